@@ -0,0 +1,19 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use fig::macros::Macros;
+
+// compares the old line by line loader against the memory mapped one on
+// the biggest file we have lying around
+fn load(c: &mut Criterion) {
+    let path = "test2.gcode";
+    let size = std::fs::metadata(path).expect("Error opening file!").len();
+
+    let mut group = c.benchmark_group("load test2.gcode");
+    group.throughput(Throughput::Bytes(size));
+    group.sample_size(20);
+    group.bench_function("buffered", |b| b.iter(|| fig::loader::load_buffered(path).unwrap()));
+    group.bench_function("mmap", |b| b.iter(|| fig::loader::open(path, &Macros::default()).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
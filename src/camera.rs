@@ -8,8 +8,35 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.5, 1.0,
 );
 
+// how the camera gets around
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mode {
+    // round the target, looking at it from above and in front
+    Orbit,
+    // straight down on the bed, turning about the target
+    Top,
+}
+
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Orbit => "orbit",
+            Mode::Top => "top",
+        }
+    }
+
+    // which way the camera looks at the target from
+    fn direction(&self) -> cgmath::Vector3<f32> {
+        use cgmath::InnerSpace;
+        match self {
+            Mode::Orbit => cgmath::Vector3::new(0.0, 1.0, 1.0).normalize(),
+            Mode::Top => cgmath::Vector3::unit_z(),
+        }
+    }
+}
 
 pub struct Camera {
+    pub mode: Mode,
     pub eye: cgmath::Point3<f32>,
     pub target: cgmath::Point3<f32>,
     pub up: cgmath::Vector3<f32>,
@@ -20,7 +47,39 @@ pub struct Camera {
 }
 
 impl Camera {
-    fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+    // point the camera at the middle of the box and back it off far enough
+    // that the whole thing fits in view
+    pub fn frame(&mut self, min: [f32; 3], max: [f32; 3]) {
+        use cgmath::{Angle, InnerSpace};
+        let min = cgmath::Point3::from(min);
+        let max = cgmath::Point3::from(max);
+        let center = cgmath::Point3::new(
+            (min.x + max.x) * 0.5,
+            (min.y + max.y) * 0.5,
+            (min.z + max.z) * 0.5,
+        );
+        let radius = ((max - min).magnitude() * 0.5).max(1.0);
+        let distance = radius / (cgmath::Deg(self.fovy) * 0.5).sin();
+
+        self.target = center;
+        self.eye = center + self.mode.direction() * distance;
+        self.zfar = distance + radius * 2.0;
+    }
+
+    // switches between orbiting and looking straight down, from the same
+    // distance either way
+    pub fn toggle_mode(&mut self) {
+        use cgmath::InnerSpace;
+        let distance = (self.eye - self.target).magnitude();
+        self.mode = match self.mode {
+            Mode::Orbit => Mode::Top,
+            Mode::Top => Mode::Orbit,
+        };
+        self.eye = self.target + self.mode.direction() * distance;
+        self.up = cgmath::Vector3::unit_y();
+    }
+
+    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
         // 1.
         let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
         // 2.
@@ -122,6 +181,21 @@ impl CameraController {
             camera.eye -= forward_norm * self.speed;
         }
 
+        if camera.mode == Mode::Top {
+            // turning the picture round keeps it looking straight down,
+            // about as far as orbiting would have gone
+            let turn = self.speed / forward_mag.max(self.speed);
+            let turn = match (self.is_left_pressed, self.is_right_pressed) {
+                (true, false) => turn,
+                (false, true) => -turn,
+                _ => return,
+            };
+            let (sin, cos) = turn.sin_cos();
+            let up = camera.up;
+            camera.up = cgmath::Vector3::new(up.x * cos - up.y * sin, up.x * sin + up.y * cos, 0.0);
+            return;
+        }
+
         let right = forward_norm.cross(camera.up);
 
         // Redo radius calc in case the fowrard/backward is pressed.
@@ -0,0 +1,71 @@
+use fig::macros::{Command, Macros};
+use fig::toolpath::Layer;
+
+use crate::hud;
+use crate::text::{self, Anchor};
+
+// past this the list gets cut short
+const ROWS: usize = 24;
+// and so do long commands
+const COMMAND_LENGTH: usize = 48;
+
+// klipper macros, extended commands and M98 calls on the layer being
+// looked at, in the bottom left corner above the inspector
+pub struct CommandPanel {
+    pub panel: hud::Panel,
+}
+
+impl CommandPanel {
+    pub fn new(text: &mut text::Layer) -> Self {
+        Self { panel: hud::Panel::new(text, Anchor::BottomLeft) }
+    }
+
+    // call once per frame. macros are whatever definitions were loaded for
+    // expanding
+    pub fn update(
+        &mut self,
+        text: &mut text::Layer,
+        commands: &[Command],
+        macros: &Macros,
+        layers: &[Layer],
+        layer: Option<usize>,
+    ) {
+        if !self.panel.refresh(layer) {
+            return;
+        }
+
+        let shown: &[Command] = match layer.and_then(|l| layers.get(l).map(|range| (l, range))) {
+            Some((l, range)) => {
+                let start = commands.partition_point(|command| command.before < range.moves.start);
+                // the end macro comes after the last move
+                let end = if l + 1 == layers.len() {
+                    commands.len()
+                } else {
+                    commands.partition_point(|command| command.before < range.moves.end)
+                };
+                &commands[start..end]
+            },
+            None => commands,
+        };
+
+        let mut header = format!("{} in print", commands.len());
+        if !macros.is_empty() {
+            header += &format!("  expanding {} macros", macros.len());
+        }
+        let names: Vec<String> = shown.iter().take(ROWS).map(|command| format!("line {}", command.line)).collect();
+        let mut rows = vec![("commands", header)];
+        for (name, command) in names.iter().zip(shown.iter()) {
+            let mut call = command.name.clone();
+            for (key, value) in command.params.iter() {
+                call += &format!(" {}={}", key, value);
+            }
+            let call: String = call.chars().take(COMMAND_LENGTH).collect();
+            let expanded = if macros.contains(&command.name) { "  expanded" } else { "" };
+            rows.push((name.as_str(), format!("{}  {}{}", call, command.kind.name(), expanded)));
+        }
+        if shown.len() > ROWS {
+            rows.push(("", format!("{} more", shown.len() - ROWS)));
+        }
+        self.panel.set_rows(text, &rows);
+    }
+}
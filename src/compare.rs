@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+
+use fig::diff::{self, Diff, Side};
+use fig::toolpath::{Layer, Toolpath};
+
+use crate::hud;
+use crate::text::{self, Anchor};
+
+pub const FRAGMENT_ENTRY: &str = "fs_compare";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum View {
+    // both in the same place, the other file in its own color
+    Overlay,
+    // this file on the left, the other on the right, one camera for both
+    Split,
+    // just this file
+    Off,
+}
+
+impl View {
+    pub fn next(self) -> Self {
+        match self {
+            View::Overlay => View::Split,
+            View::Split => View::Off,
+            View::Off => View::Overlay,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            View::Overlay => "overlay",
+            View::Split => "side by side",
+            View::Off => "off",
+        }
+    }
+}
+
+// the file passed with --compare. it doesnt get any of the panels, just
+// drawn along with the one being looked at
+pub struct Other {
+    path: PathBuf,
+    layers: Vec<Layer>,
+    buffer: wgpu::Buffer,
+    vertex_count: u32,
+    // against whatever file is open, which is the first side
+    diff: Diff,
+}
+
+impl Other {
+    pub fn new(device: &wgpu::Device, path: PathBuf, toolpath: &Toolpath, base: Side, side: Side) -> Self {
+        let (buffer, vertex_count) = crate::upload_toolpath(device, toolpath);
+        Self {
+            path,
+            layers: toolpath.layers(),
+            buffer,
+            vertex_count,
+            diff: Diff::new(base, side),
+        }
+    }
+
+    // the open file changed, so does what this one is compared against
+    pub fn set_base(&mut self, side: Side) {
+        self.diff.sides[0] = side;
+    }
+
+    // layers go by index, the same number up from the bed in both files
+    // even if they arent at the same height
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, pipeline: &'a wgpu::RenderPipeline, layer: Option<usize>) {
+        let visible = match layer.map(|l| self.layers.get(l).or(self.layers.last())) {
+            Some(Some(layer)) => (layer.moves.end as u32 + 1).min(self.vertex_count),
+            Some(None) => 0,
+            None => self.vertex_count,
+        };
+        if visible < 2 {
+            return;
+        }
+        pass.set_pipeline(pipeline);
+        pass.set_vertex_buffer(0, self.buffer.slice(..));
+        pass.draw(0..visible, 0..1);
+    }
+}
+
+// how the two files differ, in the bottom left corner above the command
+// list
+pub struct DiffPanel {
+    pub panel: hud::Panel,
+    view: View,
+}
+
+impl DiffPanel {
+    pub fn new(text: &mut text::Layer) -> Self {
+        Self { panel: hud::Panel::new(text, Anchor::BottomLeft), view: View::Overlay }
+    }
+
+    pub fn view(&self) -> View {
+        self.view
+    }
+
+    pub fn cycle(&mut self) {
+        self.view = self.view.next();
+        self.panel.invalidate();
+    }
+
+    // call once per frame
+    pub fn update(&mut self, text: &mut text::Layer, other: Option<&Other>, layer: Option<usize>) {
+        if !self.panel.refresh(layer) {
+            return;
+        }
+
+        let Some(other) = other else {
+            self.panel.set_rows(text, &[("compare", String::from("nothing, open with --compare"))]);
+            return;
+        };
+        let [a, b] = &other.diff.sides;
+        let name = other.path.file_name().unwrap_or(other.path.as_os_str()).to_string_lossy();
+        let mut rows = vec![
+            ("compare", format!("{}  {}", name, self.view.name())),
+            (
+                "layers",
+                format!("{} / {}  {:+}", a.layers.len(), b.layers.len(), b.layers.len() as i64 - a.layers.len() as i64),
+            ),
+            (
+                "time",
+                format!(
+                    "{} / {}  {}",
+                    hud::format_duration(a.time),
+                    hud::format_duration(b.time),
+                    diff::signed_duration(b.time - a.time),
+                ),
+            ),
+            (
+                "filament",
+                format!(
+                    "{:.2}mm / {:.2}mm  {}",
+                    a.filament.length,
+                    b.filament.length,
+                    diff::signed_change(a.filament.length, b.filament.length, "mm"),
+                ),
+            ),
+        ];
+        if let Some(layer) = layer.and_then(|l| other.diff.layer(l)) {
+            rows.push((
+                "this layer",
+                format!(
+                    "{:.1}mm / {:.1}mm  {}",
+                    layer.length[0],
+                    layer.length[1],
+                    diff::signed_change(layer.length[0], layer.length[1], "mm"),
+                ),
+            ));
+        }
+        rows.push(("changed", format!("{} of {} layers", other.diff.changed().count(), other.diff.layer_count())));
+        self.panel.set_rows(text, &rows);
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+// the `; key = value` settings prusa and super slicer dump at the end of
+// every file (and the handful of summary lines like `; filament used [g]`
+// just above them)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the key and value of a setting line, if it is one
+    pub fn parse_line(line: &str) -> Option<(&str, &str)> {
+        let setting = line.strip_prefix("; ")?;
+        let (key, value) = setting.split_once(" = ")?;
+        if key.is_empty() || key.starts_with(' ') {
+            return None;
+        }
+        Some((key, value.trim_end()))
+    }
+
+    // later values win, same as the slicer reading its own config back
+    pub fn insert(&mut self, key: &str, value: &str) {
+        self.values.insert(String::from(key), String::from(value));
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub fn get_f32(&self, key: &str) -> Option<f32> {
+        self.get(key)?.trim().parse().ok()
+    }
+
+    // per extruder settings are comma separated, one value each
+    pub fn get_list(&self, key: &str) -> Vec<f32> {
+        self.get(key)
+            .map(|value| value.split(',').filter_map(|v| v.trim().parse().ok()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
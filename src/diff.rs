@@ -0,0 +1,232 @@
+use std::fmt;
+
+use crate::info::format_duration;
+use crate::planner::{self, Limits};
+use crate::stats::{self, Filament, Usage};
+use crate::toolpath::{Layer, Toolpath};
+
+// layers whose path length is closer than this count as the same, mm.
+// reslicing the same model moves things around by rounding errors
+const LENGTH_TOLERANCE: f32 = 0.05;
+
+// what gets compared about each file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Side {
+    pub moves: usize,
+    pub filament: Usage,
+    // seconds
+    pub time: f32,
+    pub slicer_time: Option<f32>,
+    // mm of travel
+    pub travel: f32,
+    // the height and extruded path length of each layer, lines up with
+    // Toolpath::layers
+    pub layers: Vec<(f32, f32)>,
+}
+
+impl Side {
+    // with the time and filament already worked out, like the viewer has
+    // them
+    pub fn new(toolpath: &Toolpath, layers: &[Layer], filament: Usage, time: f32, slicer_time: Option<f32>) -> Self {
+        let travel = toolpath.moves.iter().filter(|m| !m.is_extrusion()).map(|m| m.length()).sum();
+        let layers = layers
+            .iter()
+            .map(|layer| {
+                let length = toolpath.moves[layer.moves.clone()]
+                    .iter()
+                    .filter(|m| m.is_extrusion())
+                    .map(|m| m.length())
+                    .sum();
+                (layer.z, length)
+            })
+            .collect();
+        Self { moves: toolpath.len(), filament, time, slicer_time, travel, layers }
+    }
+
+    // runs the planner and adds up the filament itself
+    pub fn measure(toolpath: &Toolpath, filaments: &[Filament]) -> Self {
+        let layers = toolpath.layers();
+        let filament = stats::filament(toolpath, &layers, filaments).total;
+        let estimate = planner::estimate(toolpath, Limits::for_toolpath(toolpath));
+        let comparison = planner::compare(toolpath, &estimate);
+        Self::new(toolpath, &layers, filament, comparison.planner, comparison.slicer)
+    }
+}
+
+// one layer in both files, matched up by index since reslicing with a
+// different first layer height shifts every z after it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LayerDiff {
+    pub index: usize,
+    // None where that file has run out of layers
+    pub z: [Option<f32>; 2],
+    // extruded path length, mm
+    pub length: [f32; 2],
+}
+
+impl LayerDiff {
+    pub fn change(&self) -> f32 {
+        self.length[1] - self.length[0]
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.z[0] != self.z[1] || self.change().abs() > LENGTH_TOLERANCE
+    }
+}
+
+// two files side by side, the first being the one things changed from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diff {
+    pub sides: [Side; 2],
+}
+
+impl Diff {
+    pub fn new(a: Side, b: Side) -> Self {
+        Self { sides: [a, b] }
+    }
+
+    // whichever file has more of them
+    pub fn layer_count(&self) -> usize {
+        self.sides[0].layers.len().max(self.sides[1].layers.len())
+    }
+
+    pub fn layer(&self, index: usize) -> Option<LayerDiff> {
+        if index >= self.layer_count() {
+            return None;
+        }
+        let [a, b] = [&self.sides[0], &self.sides[1]].map(|side| side.layers.get(index).copied());
+        Some(LayerDiff {
+            index,
+            z: [a.map(|(z, _)| z), b.map(|(z, _)| z)],
+            length: [a.map(|(_, length)| length).unwrap_or(0.0), b.map(|(_, length)| length).unwrap_or(0.0)],
+        })
+    }
+
+    // the layers that print something different, in order
+    pub fn changed(&self) -> impl Iterator<Item = LayerDiff> + '_ {
+        (0..self.layer_count()).filter_map(|index| self.layer(index)).filter(|layer| layer.is_changed())
+    }
+}
+
+// +1m 02s, -5s
+pub fn signed_duration(seconds: f32) -> String {
+    format!("{}{}", if seconds < 0.0 { "-" } else { "+" }, format_duration(seconds.abs()))
+}
+
+// +12.30mm (+2.1%), without the percentage when the first is zero
+pub fn signed_change(from: f32, to: f32, unit: &str) -> String {
+    let change = to - from;
+    if from == 0.0 {
+        format!("{:+.2}{}", change, unit)
+    } else {
+        format!("{:+.2}{} ({:+.1}%)", change, unit, change / from * 100.0)
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [a, b] = &self.sides;
+        let row = |f: &mut fmt::Formatter, name: &str, a: String, b: String, change: String| {
+            writeln!(f, "{:<12}{:>16}{:>16}  {}", name, a, b, change)
+        };
+        row(f, "", String::from("a"), String::from("b"), String::from("change"))?;
+        row(
+            f,
+            "layers",
+            a.layers.len().to_string(),
+            b.layers.len().to_string(),
+            format!("{:+}", b.layers.len() as i64 - a.layers.len() as i64),
+        )?;
+        row(f, "moves", a.moves.to_string(), b.moves.to_string(), format!("{:+}", b.moves as i64 - a.moves as i64))?;
+        row(f, "time", format_duration(a.time), format_duration(b.time), signed_duration(b.time - a.time))?;
+        if let (Some(a), Some(b)) = (a.slicer_time, b.slicer_time) {
+            row(f, "slicer time", format_duration(a), format_duration(b), signed_duration(b - a))?;
+        }
+        row(
+            f,
+            "filament",
+            format!("{:.2}mm", a.filament.length),
+            format!("{:.2}mm", b.filament.length),
+            signed_change(a.filament.length, b.filament.length, "mm"),
+        )?;
+        row(
+            f,
+            "weight",
+            format!("{:.2}g", a.filament.weight),
+            format!("{:.2}g", b.filament.weight),
+            signed_change(a.filament.weight, b.filament.weight, "g"),
+        )?;
+        row(
+            f,
+            "travel",
+            format!("{:.0}mm", a.travel),
+            format!("{:.0}mm", b.travel),
+            signed_change(a.travel, b.travel, "mm"),
+        )?;
+
+        let changed: Vec<LayerDiff> = self.changed().collect();
+        write!(f, "changed     {} of {} layers", changed.len(), self.layer_count())?;
+        for layer in changed.iter() {
+            let z = layer.z.map(|z| z.map(|z| format!("{:.2}", z)).unwrap_or_else(|| String::from("-")));
+            write!(
+                f,
+                "\n  layer {:<5} z {:>7} {:>7}  {:>10.1}mm {:>10.1}mm  {}",
+                layer.index + 1,
+                z[0],
+                z[1],
+                layer.length[0],
+                layer.length[1],
+                signed_change(layer.length[0], layer.length[1], "mm"),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+
+    fn side(layers: &[(f32, f32)]) -> Side {
+        Side { layers: layers.to_vec(), ..Side::default() }
+    }
+
+    #[test]
+    fn changed_skips_layers_within_the_tolerance() {
+        let a = side(&[(0.2, 100.0), (0.4, 80.0), (0.6, 80.0)]);
+        let b = side(&[(0.2, 100.02), (0.4, 90.0), (0.65, 80.0), (0.85, 20.0)]);
+        let diff = Diff::new(a.clone(), b);
+
+        let changed: Vec<(usize, [Option<f32>; 2])> = diff.changed().map(|layer| (layer.index, layer.z)).collect();
+        assert_eq!(
+            changed,
+            vec![
+                (1, [Some(0.4), Some(0.4)]),
+                (2, [Some(0.6), Some(0.65)]),
+                // the layer only one of them has
+                (3, [None, Some(0.85)]),
+            ],
+        );
+        assert_eq!(diff.layer(3).map(|layer| layer.change()), Some(20.0));
+        assert_eq!(diff.layer(4), None);
+        assert_eq!(Diff::new(a.clone(), a).changed().count(), 0);
+    }
+
+    #[test]
+    fn sides_measure_extruded_length_per_layer() {
+        let toolpath = loader::load_bytes(b"M83\nG1 Z0.2 F3000\nG1 X10 Y0 E1\nG1 X10 Y20\nG1 Z0.4\nG1 X10 Y25 E1\n");
+        let layers = toolpath.layers();
+        let side = Side::new(&toolpath, &layers, Usage::default(), 60.0, None);
+        assert_eq!(side.layers, vec![(0.2, 10.0), (0.4, 5.0)]);
+        assert!((side.travel - 20.4).abs() < 1e-4, "{}", side.travel);
+    }
+
+    #[test]
+    fn signed_changes() {
+        assert_eq!(signed_duration(-5.0), "-5s");
+        assert_eq!(signed_duration(62.0), "+1m 02s");
+        assert_eq!(signed_change(200.0, 204.2, "mm"), "+4.20mm (+2.1%)");
+        assert_eq!(signed_change(0.0, 3.0, "g"), "+3.00g");
+    }
+}
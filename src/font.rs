@@ -0,0 +1,43 @@
+pub mod portable;
+#[cfg(all(windows, feature = "directwrite"))]
+pub mod directwrite;
+
+// a rasterized glyph, one byte of coverage per pixel
+pub struct Bitmap {
+    pub width: u32,
+    pub height: u32,
+    // offset of the top left corner from the pen position on the baseline,
+    // with y going down like the bitmap rows do
+    pub left: f32,
+    pub top: f32,
+    pub pixels: Vec<u8>,
+}
+
+// everything the text renderer needs out of a font. sizes are in pixels
+// from the top of the ascender to the bottom of the descender
+pub trait Backend {
+    fn advance(&self, c: char, size: f32) -> f32;
+    fn kern(&self, previous: char, c: char, size: f32) -> f32;
+    fn ascent(&self, size: f32) -> f32;
+    // baseline to baseline
+    fn line_height(&self, size: f32) -> f32 {
+        size
+    }
+    // None for whitespace and anything else without an outline
+    fn rasterize(&self, c: char, size: f32) -> Option<Bitmap>;
+
+    // a hollow box for characters the font doesnt have
+    fn missing(&self, size: f32) -> Bitmap {
+        let width = (self.advance('0', size) as u32).max(3) - 2;
+        let height = (self.ascent(size) as u32).max(2);
+        let mut pixels = vec![0; (width * height) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                if x == 0 || x == width - 1 || y == 0 || y == height - 1 {
+                    pixels[(y * width + x) as usize] = 255;
+                }
+            }
+        }
+        Bitmap { width, height, left: 1.0, top: -(height as f32), pixels }
+    }
+}
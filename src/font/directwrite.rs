@@ -0,0 +1,224 @@
+use com_wrapper::ComWrapper;
+use dcommon::Error;
+use winapi::shared::minwindef::FALSE;
+use winapi::shared::windef::RECT;
+use winapi::shared::winerror::{E_FAIL, HRESULT, SUCCEEDED};
+use winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL;
+use winapi::um::dwrite::{
+    DWriteCreateFactory, IDWriteFactory, DWRITE_FACTORY_TYPE_SHARED,
+    IDWriteFontFace, IDWriteGlyphRunAnalysis, DWRITE_FONT_METRICS, DWRITE_GLYPH_METRICS,
+    DWRITE_GLYPH_OFFSET, DWRITE_GLYPH_RUN, DWRITE_FONT_WEIGHT_NORMAL, DWRITE_FONT_STRETCH_NORMAL,
+    DWRITE_FONT_STYLE_NORMAL, DWRITE_RENDERING_MODE_NATURAL, DWRITE_TEXTURE_CLEARTYPE_3x1,
+};
+use winapi::um::unknwnbase::IUnknown;
+use winapi::Interface;
+use wio::com::ComPtr;
+
+use super::{Backend, Bitmap};
+
+#[repr(transparent)]
+#[derive(Clone, ComWrapper)]
+#[com(send, sync, debug)]
+pub struct Factory {
+    ptr: ComPtr<IDWriteFactory>,
+}
+
+// stolen from: https://github.com/Connicpu/directwrite-rs/tree/master
+
+impl Factory {
+    pub fn new() -> Result<Factory, Error> {
+        unsafe {
+            let mut ptr: *mut IDWriteFactory = std::ptr::null_mut();
+            let hr = DWriteCreateFactory(
+                DWRITE_FACTORY_TYPE_SHARED,
+                &IDWriteFactory::uuidof(),
+                &mut ptr as *mut _ as *mut *mut IUnknown,
+            );
+
+            if SUCCEEDED(hr) {
+                Ok(Factory::from_raw(ptr))
+            } else {
+                Err(hr.into())
+            }
+        }
+    }
+}
+
+// not sure what i need these for but:
+pub unsafe trait IFactory {
+    unsafe fn raw_f(&self) -> &IDWriteFactory;
+}
+
+unsafe impl IFactory for Factory {
+    unsafe fn raw_f(&self) -> &IDWriteFactory {
+        &self.ptr
+    }
+}
+
+fn check(hr: HRESULT) -> Result<(), Error> {
+    if SUCCEEDED(hr) {
+        Ok(())
+    } else {
+        Err(hr.into())
+    }
+}
+
+// a system font looked up and rasterized through directwrite, so windows
+// users get the same hinting/antialiasing as everything else on their
+// desktop
+pub struct Font {
+    factory: Factory,
+    face: ComPtr<IDWriteFontFace>,
+    metrics: DWRITE_FONT_METRICS,
+}
+
+impl Font {
+    pub fn system(family: &str) -> Result<Self, Error> {
+        let factory = Factory::new()?;
+        let name: Vec<u16> = family.encode_utf16().chain(std::iter::once(0)).collect();
+
+        unsafe {
+            let mut collection = std::ptr::null_mut();
+            check(factory.raw_f().GetSystemFontCollection(&mut collection, FALSE))?;
+            let collection = ComPtr::from_raw(collection);
+
+            let mut index = 0;
+            let mut exists = FALSE;
+            check(collection.FindFamilyName(name.as_ptr(), &mut index, &mut exists))?;
+            if exists == FALSE {
+                return Err(E_FAIL.into());
+            }
+
+            let mut family = std::ptr::null_mut();
+            check(collection.GetFontFamily(index, &mut family))?;
+            let family = ComPtr::from_raw(family);
+
+            let mut font = std::ptr::null_mut();
+            check(family.GetFirstMatchingFont(
+                DWRITE_FONT_WEIGHT_NORMAL,
+                DWRITE_FONT_STRETCH_NORMAL,
+                DWRITE_FONT_STYLE_NORMAL,
+                &mut font,
+            ))?;
+            let font = ComPtr::from_raw(font);
+
+            let mut face = std::ptr::null_mut();
+            check(font.CreateFontFace(&mut face))?;
+            let face = ComPtr::from_raw(face);
+
+            let mut metrics = std::mem::zeroed();
+            face.GetMetrics(&mut metrics);
+
+            Ok(Self { factory, face, metrics })
+        }
+    }
+
+    fn glyph_index(&self, c: char) -> u16 {
+        let code_point = c as u32;
+        let mut index = 0;
+        unsafe {
+            self.face.GetGlyphIndices(&code_point, 1, &mut index);
+        }
+        index
+    }
+
+    // directwrite sizes fonts by the em, the other backends go by the
+    // ascender to descender height
+    fn em_size(&self, size: f32) -> f32 {
+        let height = self.metrics.ascent as f32 + self.metrics.descent as f32;
+        size * self.metrics.designUnitsPerEm as f32 / height
+    }
+
+    fn design_to_pixels(&self, units: f32, size: f32) -> f32 {
+        units * self.em_size(size) / self.metrics.designUnitsPerEm as f32
+    }
+}
+
+impl Backend for Font {
+    fn advance(&self, c: char, size: f32) -> f32 {
+        let index = self.glyph_index(c);
+        let mut metrics: DWRITE_GLYPH_METRICS = unsafe { std::mem::zeroed() };
+        unsafe {
+            self.face.GetDesignGlyphMetrics(&index, 1, &mut metrics, FALSE);
+        }
+        self.design_to_pixels(metrics.advanceWidth as f32, size)
+    }
+
+    // pair kerning needs IDWriteFontFace1, monospace fonts dont kern anyway
+    fn kern(&self, _previous: char, _c: char, _size: f32) -> f32 {
+        0.0
+    }
+
+    fn ascent(&self, size: f32) -> f32 {
+        self.design_to_pixels(self.metrics.ascent as f32, size)
+    }
+
+    fn line_height(&self, size: f32) -> f32 {
+        size + self.design_to_pixels(self.metrics.lineGap as f32, size)
+    }
+
+    fn rasterize(&self, c: char, size: f32) -> Option<Bitmap> {
+        let index = self.glyph_index(c);
+        if index == 0 {
+            return Some(self.missing(size));
+        }
+
+        let advance = 0.0;
+        let offset = DWRITE_GLYPH_OFFSET { advanceOffset: 0.0, ascenderOffset: 0.0 };
+        let run = DWRITE_GLYPH_RUN {
+            fontFace: self.face.as_raw(),
+            fontEmSize: self.em_size(size),
+            glyphCount: 1,
+            glyphIndices: &index,
+            glyphAdvances: &advance,
+            glyphOffsets: &offset,
+            isSideways: FALSE,
+            bidiLevel: 0,
+        };
+
+        unsafe {
+            let mut analysis: *mut IDWriteGlyphRunAnalysis = std::ptr::null_mut();
+            check(self.factory.raw_f().CreateGlyphRunAnalysis(
+                &run,
+                1.0,
+                std::ptr::null(),
+                DWRITE_RENDERING_MODE_NATURAL,
+                DWRITE_MEASURING_MODE_NATURAL,
+                0.0,
+                0.0,
+                &mut analysis,
+            )).ok()?;
+            let analysis = ComPtr::from_raw(analysis);
+
+            // bounds are relative to the pen on the baseline, y down
+            let mut bounds: RECT = std::mem::zeroed();
+            check(analysis.GetAlphaTextureBounds(DWRITE_TEXTURE_CLEARTYPE_3x1, &mut bounds)).ok()?;
+            let width = (bounds.right - bounds.left) as u32;
+            let height = (bounds.bottom - bounds.top) as u32;
+            if width == 0 || height == 0 {
+                return None;
+            }
+
+            // natural rendering only hands out cleartype textures, three
+            // bytes per pixel. we just want the coverage so average them
+            let mut rgb = vec![0u8; (width * height * 3) as usize];
+            check(analysis.CreateAlphaTexture(
+                DWRITE_TEXTURE_CLEARTYPE_3x1,
+                &bounds,
+                rgb.as_mut_ptr(),
+                rgb.len() as u32,
+            )).ok()?;
+            let pixels = rgb.chunks_exact(3)
+                .map(|p| ((p[0] as u32 + p[1] as u32 + p[2] as u32) / 3) as u8)
+                .collect();
+
+            Some(Bitmap {
+                width,
+                height,
+                left: bounds.left as f32,
+                top: bounds.top as f32,
+                pixels,
+            })
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use ab_glyph::{Font as _, FontVec, PxScale, ScaleFont};
+
+use super::{Backend, Bitmap};
+
+// truetype/opentype fonts rasterized with ab_glyph. works everywhere, so
+// its what we use unless something else is asked for
+pub struct Font {
+    font: FontVec,
+}
+
+impl Font {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, ab_glyph::InvalidFont> {
+        Ok(Self { font: FontVec::try_from_vec(bytes)? })
+    }
+
+    pub fn open(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::from_bytes(bytes)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    // dejavu sans mono, so theres always something to draw with
+    pub fn bundled() -> Self {
+        Self::from_bytes(include_bytes!("../resources/DejaVuSansMono.ttf").to_vec())
+            .expect("bundled font is valid")
+    }
+}
+
+impl Backend for Font {
+    fn advance(&self, c: char, size: f32) -> f32 {
+        let font = self.font.as_scaled(PxScale::from(size));
+        font.h_advance(self.font.glyph_id(c))
+    }
+
+    fn kern(&self, previous: char, c: char, size: f32) -> f32 {
+        let font = self.font.as_scaled(PxScale::from(size));
+        font.kern(self.font.glyph_id(previous), self.font.glyph_id(c))
+    }
+
+    fn ascent(&self, size: f32) -> f32 {
+        self.font.as_scaled(PxScale::from(size)).ascent()
+    }
+
+    fn line_height(&self, size: f32) -> f32 {
+        let font = self.font.as_scaled(PxScale::from(size));
+        font.height() + font.line_gap()
+    }
+
+    fn rasterize(&self, c: char, size: f32) -> Option<Bitmap> {
+        let id = self.font.glyph_id(c);
+        if id.0 == 0 {
+            return Some(self.missing(size));
+        }
+
+        let outline = self.font.outline_glyph(id.with_scale(size))?;
+        let bounds = outline.px_bounds();
+        let width = bounds.width() as u32;
+        let height = bounds.height() as u32;
+        let mut pixels = vec![0; (width * height) as usize];
+        outline.draw(|x, y, coverage| {
+            if x < width && y < height {
+                pixels[(y * width + x) as usize] = (coverage.clamp(0.0, 1.0) * 255.0) as u8;
+            }
+        });
+
+        Some(Bitmap {
+            width,
+            height,
+            left: bounds.min.x,
+            top: bounds.min.y,
+            pixels,
+        })
+    }
+}
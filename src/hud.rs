@@ -0,0 +1,230 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use fig::planner::Estimate;
+use fig::toolpath::Layer;
+
+use crate::camera::Camera;
+use crate::playback::Playback;
+use crate::text::{self, Anchor, LabelId, Position, Span, Style};
+
+pub use fig::info::format_duration;
+
+const KEY: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+const VALUE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+// redrawing the text every frame makes the frame time unreadable
+const REFRESH: Duration = Duration::from_millis(250);
+
+// everything the hud shows, gathered up by Fig each frame
+pub struct Info<'a> {
+    pub path: &'a Path,
+    pub layers: &'a [Layer],
+    pub layer: Option<usize>,
+    pub moves: usize,
+    pub estimate: &'a Estimate,
+    // seconds
+    pub slicer_estimate: Option<f32>,
+    pub camera: &'a Camera,
+    pub playback: &'a Playback,
+    // moves outside the build volume, if theres one to go by
+    pub outside: Option<usize>,
+}
+
+// the info panel in the top left corner
+pub struct Hud {
+    label: LabelId,
+    visible: bool,
+    last_frame: Instant,
+    last_refresh: Instant,
+    // seconds, smoothed over the last few frames
+    frame_time: f32,
+}
+
+impl Hud {
+    pub fn new(text: &mut text::Layer) -> Self {
+        let label = text.add(text::Label {
+            size: 14,
+            ..text::Label::new("", Position::Screen([8.0, 8.0]))
+        });
+        let now = Instant::now();
+        Self {
+            label,
+            visible: true,
+            last_frame: now,
+            last_refresh: now - REFRESH,
+            frame_time: 0.0,
+        }
+    }
+
+    pub fn toggle(&mut self, text: &mut text::Layer) {
+        self.visible = !self.visible;
+        if let Some(label) = text.get_mut(self.label) {
+            label.visible = self.visible;
+        }
+    }
+
+    // call once per frame
+    pub fn update(&mut self, text: &mut text::Layer, info: &Info) {
+        let now = Instant::now();
+        let elapsed = (now - self.last_frame).as_secs_f32();
+        self.last_frame = now;
+        self.frame_time += (elapsed - self.frame_time) * 0.1;
+
+        if !self.visible || now - self.last_refresh < REFRESH {
+            return;
+        }
+        self.last_refresh = now;
+        text.set_spans(self.label, &self.spans(info));
+    }
+
+    fn spans(&self, info: &Info) -> Vec<Span> {
+        let file = info.path
+            .file_name()
+            .unwrap_or(info.path.as_os_str())
+            .to_string_lossy();
+        let layer = match info.layer.and_then(|l| info.layers.get(l).map(|layer| (l, layer))) {
+            Some((l, layer)) => format!(
+                "{} / {}  z {:.2}  {}",
+                l + 1,
+                info.layers.len(),
+                layer.z,
+                format_duration(info.estimate.layer(layer)),
+            ),
+            None => format!("all {}", info.layers.len()),
+        };
+        let distance = {
+            use cgmath::InnerSpace;
+            (info.camera.target - info.camera.eye).magnitude()
+        };
+        let mut rows = vec![
+            ("file", file.into_owned()),
+            ("layer", layer),
+            ("moves", info.moves.to_string()),
+            ("time", match info.slicer_estimate {
+                Some(slicer) => format!("{}  slicer {}", format_duration(info.estimate.total), format_duration(slicer)),
+                None => format_duration(info.estimate.total),
+            }),
+            ("camera", format!("{}  {:.0}mm", info.camera.mode.name(), distance)),
+            ("frame", format!("{:.1}ms", self.frame_time * 1000.0)),
+        ];
+        match info.outside {
+            Some(0) => rows.push(("volume", String::from("inside"))),
+            Some(outside) => rows.push(("volume", format!("{} moves outside", outside))),
+            None => {},
+        }
+        let playback = info.playback;
+        if playback.active() {
+            rows.push(("replay", format!(
+                "{} {} / {}  x{}",
+                if playback.playing() { "playing" } else { "paused" },
+                format_duration(playback.time()),
+                format_duration(playback.total()),
+                playback.speed(),
+            )));
+        }
+
+        table(&rows)
+    }
+}
+
+// a table in one of the bottom corners that gets toggled with a key and is
+// only rebuilt when whatever its showing changes. the panel types hold one
+// each and just work out the rows
+pub struct Panel {
+    label: LabelId,
+    visible: bool,
+    // the layer the rows were last built for
+    layer: Option<usize>,
+    dirty: bool,
+}
+
+impl Panel {
+    pub fn new(text: &mut text::Layer, anchor: Anchor) -> Self {
+        let label = text.add(text::Label {
+            anchor,
+            size: 14,
+            visible: false,
+            ..text::Label::new("", Position::Screen([8.0, 8.0]))
+        });
+        Self {
+            label,
+            visible: false,
+            layer: None,
+            dirty: true,
+        }
+    }
+
+    pub fn label(&self) -> LabelId {
+        self.label
+    }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self, text: &mut text::Layer) {
+        self.set_visible(text, !self.visible);
+    }
+
+    pub fn set_visible(&mut self, text: &mut text::Layer, visible: bool) {
+        self.visible = visible;
+        if let Some(label) = text.get_mut(self.label) {
+            label.visible = visible;
+        }
+    }
+
+    // whatever its showing changed, a new file or a reload
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    // whether the rows need building again for the layer being looked at,
+    // assuming they will be if so. hidden panels wait until theyre shown
+    pub fn refresh(&mut self, layer: Option<usize>) -> bool {
+        if !self.visible || (!self.dirty && layer == self.layer) {
+            return false;
+        }
+        self.dirty = false;
+        self.layer = layer;
+        true
+    }
+
+    pub fn set_rows(&self, text: &mut text::Layer, rows: &[(&str, String)]) {
+        text.set_spans(self.label, &table(rows));
+    }
+}
+
+// puts the labels in one of the bottom corners on top of each other, from
+// the bottom up in the order given. hidden ones take up no room
+pub fn stack(text: &mut text::Layer, labels: &[LabelId]) {
+    let mut y = 8.0;
+    for &label in labels {
+        text.set_position(label, Position::Screen([8.0, y]));
+        let height = text.size(label)[1];
+        if height > 0.0 {
+            y += height + 8.0;
+        }
+    }
+}
+
+// name/value rows on a dark box, for the hud and anything else that wants
+// to look like it
+pub fn table(rows: &[(&str, String)]) -> Vec<Span> {
+    // pad every row out to the same length so the backgrounds line up
+    // into a box
+    let names = rows.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+    let width = rows.iter().map(|(_, value)| value.chars().count()).max().unwrap_or(0);
+    let key = Style { background: Some(BACKGROUND), ..Style::color(KEY) };
+    let value = Style { background: Some(BACKGROUND), ..Style::color(VALUE) };
+    let mut spans = Vec::new();
+    for (i, (name, text)) in rows.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::new("\n", value));
+        }
+        spans.push(Span::new(&format!(" {:<names$}  ", name, names = names), key));
+        spans.push(Span::new(&format!("{:<width$} ", text, width = width), value));
+    }
+    spans
+}
@@ -0,0 +1,463 @@
+use std::fmt::{self, Write};
+
+use crate::macros;
+use crate::objects;
+use crate::planner::{self, Limits};
+use crate::retraction::{self, Counts, Kind};
+use crate::source::Source;
+use crate::stats::{self, Filament, Usage};
+use crate::tools;
+use crate::toolpath::{Bounds, Feature, Toolpath};
+
+// who made the file, from the first comment slicers put at the top
+#[derive(Clone, Debug, PartialEq)]
+pub struct Slicer {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl Slicer {
+    // `; generated by SuperSlicer 2.4 on ...` from prusa and friends,
+    // `;Generated with Cura_SteamEngine 5.4.0` from cura
+    pub fn parse(line: &str) -> Option<Self> {
+        let comment = line.trim_start_matches(';').trim();
+        let lower = comment.to_ascii_lowercase();
+        let rest = ["generated by ", "generated with "]
+            .iter()
+            .find_map(|prefix| lower.starts_with(prefix).then(|| &comment[prefix.len()..]))?;
+        let mut words = rest.split_whitespace();
+        let name = words.next()?;
+        let version = words.next().filter(|v| *v != "on" && v.starts_with(|c: char| c.is_ascii_digit()));
+        Some(Self {
+            name: String::from(name),
+            version: version.map(String::from),
+        })
+    }
+}
+
+// a preview image embedded in the comments
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    // length of the base64 text
+    pub size: u32,
+}
+
+impl Thumbnail {
+    // `; thumbnail begin 32x32 2548`, or thumbnail_JPG/_QOI begin
+    pub fn parse(line: &str) -> Option<Self> {
+        let comment = line.strip_prefix(';')?.trim();
+        let mut words = comment.split_whitespace();
+        if !words.next()?.starts_with("thumbnail") || words.next()? != "begin" {
+            return None;
+        }
+        let (width, height) = words.next()?.split_once('x')?;
+        Some(Self {
+            width: width.parse().ok()?,
+            height: height.parse().ok()?,
+            size: words.next().and_then(|size| size.parse().ok()).unwrap_or(0),
+        })
+    }
+}
+
+// how many of each kind of move there are
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MoveCounts {
+    pub total: usize,
+    pub extrusions: usize,
+    pub travels: usize,
+    // filament pulled back or pushed out without moving
+    pub retractions: usize,
+    pub unretractions: usize,
+    // extrusions only, in the order each feature first shows up
+    pub features: Vec<(Feature, usize)>,
+}
+
+// everything `fig info` prints
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub slicer: Option<Slicer>,
+    pub thumbnails: Vec<Thumbnail>,
+    // of what gets printed. travel goes all over the place, starting from
+    // wherever the printer was before homing
+    pub bounds: Option<Bounds>,
+    // how far each layer is above the one under it, the first from the bed
+    pub layer_heights: Vec<f32>,
+    pub moves: MoveCounts,
+    pub filament: Usage,
+    // per extruder, T0 first
+    pub extruders: Vec<Usage>,
+    pub tool_changes: usize,
+    // the names of the objects on the bed, if the file labels them
+    pub objects: Vec<String>,
+    // extended commands and macro calls by name, how many times each, in
+    // the order they first show up
+    pub commands: Vec<(String, macros::Kind, usize)>,
+    pub retraction: Counts,
+    // seconds
+    pub planner_time: f32,
+    pub slicer_time: Option<f32>,
+}
+
+impl Summary {
+    pub fn new(toolpath: &Toolpath, source: &Source, filaments: &[Filament]) -> Self {
+        // the slicer comment is the first line, give or take a blank one
+        let slicer = (1..=source.len().min(8))
+            .filter_map(|n| source.line(n))
+            .find_map(|line| Slicer::parse(&line));
+        let thumbnails = (1..=source.len())
+            .filter_map(|n| source.line(n))
+            .filter(|line| line.starts_with(';'))
+            .filter_map(|line| Thumbnail::parse(&line))
+            .collect();
+
+        let layers = toolpath.layers();
+        let mut z = 0.0;
+        let layer_heights = layers
+            .iter()
+            .map(|layer| {
+                // to the micron, so 0.2 doesnt come out as 0.20000002
+                let height = ((layer.z - z) * 1000.0).round() / 1000.0;
+                z = layer.z;
+                height
+            })
+            .collect();
+
+        let mut bounds: Option<Bounds> = None;
+        let mut moves = MoveCounts { total: toolpath.len(), ..MoveCounts::default() };
+        for m in toolpath.moves.iter() {
+            let still = m.start[0] == m.end[0] && m.start[1] == m.end[1];
+            if m.is_extrusion() {
+                let bounds = bounds.get_or_insert(Bounds::at(m.start));
+                bounds.include(m.start);
+                bounds.include(m.end);
+                moves.extrusions += 1;
+                match moves.features.iter_mut().find(|(feature, _)| *feature == m.feature) {
+                    Some((_, count)) => *count += 1,
+                    None => moves.features.push((m.feature, 1)),
+                }
+            } else if still && m.e < 0.0 {
+                moves.retractions += 1;
+            } else if still && m.e > 0.0 {
+                moves.unretractions += 1;
+            } else {
+                moves.travels += 1;
+            }
+        }
+
+        let stats = stats::filament(toolpath, &layers, filaments);
+        let mut commands: Vec<(String, macros::Kind, usize)> = Vec::new();
+        for command in macros::commands(toolpath, source) {
+            match commands.iter_mut().find(|(name, _, _)| *name == command.name) {
+                Some((_, _, count)) => *count += 1,
+                None => commands.push((command.name, command.kind, 1)),
+            }
+        }
+        let estimate = planner::estimate(toolpath, Limits::for_toolpath(toolpath));
+        let comparison = planner::compare(toolpath, &estimate);
+        Self {
+            slicer,
+            thumbnails,
+            bounds,
+            layer_heights,
+            moves,
+            filament: stats.total,
+            extruders: stats.extruders,
+            tool_changes: tools::changes(toolpath).len(),
+            objects: objects::find(toolpath, source).objects.into_iter().map(|object| object.name).collect(),
+            commands,
+            retraction: retraction::analyze(toolpath, &layers).total,
+            planner_time: comparison.planner,
+            slicer_time: comparison.slicer,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\n");
+        let slicer = match &self.slicer {
+            Some(slicer) => format!(
+                "{{\"name\": {}, \"version\": {}}}",
+                string(&slicer.name),
+                slicer.version.as_deref().map_or(String::from("null"), string),
+            ),
+            None => String::from("null"),
+        };
+        let thumbnails: Vec<String> = self.thumbnails
+            .iter()
+            .map(|t| format!("{{\"width\": {}, \"height\": {}, \"size\": {}}}", t.width, t.height, t.size))
+            .collect();
+        let bounds = match self.bounds {
+            Some(bounds) => format!(
+                "{{\"min\": {}, \"max\": {}, \"size\": {}}}",
+                array(&bounds.min),
+                array(&bounds.max),
+                array(&bounds.size()),
+            ),
+            None => String::from("null"),
+        };
+        let features: Vec<String> = self.moves.features
+            .iter()
+            .map(|(feature, count)| format!("{}: {}", string(feature.name()), count))
+            .collect();
+        let moves = format!(
+            "{{\"total\": {}, \"extrusions\": {}, \"travels\": {}, \"retractions\": {}, \"unretractions\": {}, \"features\": {{{}}}}}",
+            self.moves.total,
+            self.moves.extrusions,
+            self.moves.travels,
+            self.moves.retractions,
+            self.moves.unretractions,
+            features.join(", "),
+        );
+        let extruders: Vec<String> = self.extruders.iter().map(usage).collect();
+        let objects: Vec<String> = self.objects.iter().map(|name| string(name)).collect();
+        let commands: Vec<String> = self.commands
+            .iter()
+            .map(|(name, kind, count)| format!(
+                "{}: {{\"kind\": {}, \"count\": {}}}",
+                string(name),
+                string(kind.name()),
+                count,
+            ))
+            .collect();
+        let tools = format!(
+            "{{\"changes\": {}, \"filament\": [{}]}}",
+            self.tool_changes,
+            extruders.join(", "),
+        );
+        let retraction: Vec<String> = Kind::ALL
+            .iter()
+            .map(|&kind| format!(
+                "{}: {{\"count\": {}, \"average\": {}}}",
+                string(kind.name()),
+                self.retraction.count(kind),
+                number(self.retraction.average(kind)),
+            ))
+            .collect();
+        let time = format!(
+            "{{\"planner\": {}, \"slicer\": {}}}",
+            number(self.planner_time),
+            self.slicer_time.map_or(String::from("null"), number),
+        );
+
+        let fields = [
+            ("slicer", slicer),
+            ("thumbnails", format!("[{}]", thumbnails.join(", "))),
+            ("bounds", bounds),
+            ("layers", self.layer_heights.len().to_string()),
+            ("layer_heights", array(&self.layer_heights)),
+            ("moves", moves),
+            ("filament", usage(&self.filament)),
+            ("tools", tools),
+            ("objects", format!("[{}]", objects.join(", "))),
+            ("commands", format!("{{{}}}", commands.join(", "))),
+            ("retraction", format!("{{{}}}", retraction.join(", "))),
+            ("time", time),
+        ];
+        for (i, (key, value)) in fields.iter().enumerate() {
+            let comma = if i + 1 < fields.len() { "," } else { "" };
+            let _ = writeln!(json, "  {}: {}{}", string(key), value, comma);
+        }
+        json.push('}');
+        json
+    }
+}
+
+// for people
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.slicer {
+            Some(Slicer { name, version: Some(version) }) => writeln!(f, "slicer      {} {}", name, version)?,
+            Some(Slicer { name, version: None }) => writeln!(f, "slicer      {}", name)?,
+            None => writeln!(f, "slicer      unknown")?,
+        }
+        if !self.thumbnails.is_empty() {
+            let sizes: Vec<String> = self.thumbnails.iter().map(|t| format!("{}x{}", t.width, t.height)).collect();
+            writeln!(f, "thumbnails  {}", sizes.join(", "))?;
+        }
+        if let Some(bounds) = self.bounds {
+            let [x, y, z] = bounds.size();
+            writeln!(f, "size        {:.2} x {:.2} x {:.2}mm", x, y, z)?;
+            writeln!(
+                f,
+                "bounds      {:.2}, {:.2}, {:.2} to {:.2}, {:.2}, {:.2}",
+                bounds.min[0], bounds.min[1], bounds.min[2],
+                bounds.max[0], bounds.max[1], bounds.max[2],
+            )?;
+        }
+        write!(f, "layers      {}", self.layer_heights.len())?;
+        if let Some((first, rest)) = self.layer_heights.split_first() {
+            write!(f, "  first {:.3}mm", first)?;
+            let min = rest.iter().copied().fold(f32::INFINITY, f32::min);
+            let max = rest.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            if min == max {
+                write!(f, "  then {:.3}mm", min)?;
+            } else if min < max {
+                write!(f, "  then {:.3} to {:.3}mm", min, max)?;
+            }
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "moves       {}  extrude {}  travel {}  retract {}  unretract {}",
+            self.moves.total,
+            self.moves.extrusions,
+            self.moves.travels,
+            self.moves.retractions,
+            self.moves.unretractions,
+        )?;
+        for (feature, count) in self.moves.features.iter() {
+            writeln!(f, "  {:<24}{}", feature.name(), count)?;
+        }
+        write!(
+            f,
+            "filament    {:.2}mm  {:.2}cm3  {:.2}g",
+            self.filament.length,
+            self.filament.volume / 1000.0,
+            self.filament.weight,
+        )?;
+        if self.filament.cost > 0.0 {
+            write!(f, "  cost {:.2}", self.filament.cost)?;
+        }
+        writeln!(f)?;
+        if self.extruders.len() > 1 {
+            writeln!(f, "tools       {}  changes {}", self.extruders.len(), self.tool_changes)?;
+            for (i, usage) in self.extruders.iter().enumerate() {
+                writeln!(f, "  T{:<22}{:.2}mm  {:.2}g", i, usage.length, usage.weight)?;
+            }
+        }
+        if !self.objects.is_empty() {
+            writeln!(f, "objects     {}", self.objects.len())?;
+            for name in self.objects.iter() {
+                writeln!(f, "  {}", name)?;
+            }
+        }
+        if !self.commands.is_empty() {
+            writeln!(f, "commands    {}", self.commands.iter().map(|(_, _, count)| count).sum::<usize>())?;
+            for (name, kind, count) in self.commands.iter() {
+                writeln!(f, "  {:<24}{}  {}", name, count, kind.name())?;
+            }
+        }
+        let retraction: Vec<String> = Kind::ALL
+            .iter()
+            .map(|&kind| format!("{} {} ({:.2}mm)", kind.name(), self.retraction.count(kind), self.retraction.average(kind)))
+            .collect();
+        writeln!(f, "retraction  {}", retraction.join("  "))?;
+        write!(f, "time        {}", format_duration(self.planner_time))?;
+        if let Some(slicer) = self.slicer_time {
+            write!(f, "  slicer {}", format_duration(slicer))?;
+        }
+        Ok(())
+    }
+}
+
+// 1h 02m 03s
+pub fn format_duration(seconds: f32) -> String {
+    let seconds = seconds.round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+// just enough json to not need a dependency for it
+
+fn string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            },
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+// json has no nan or infinity
+fn number(value: f32) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        String::from("null")
+    }
+}
+
+fn array(values: &[f32]) -> String {
+    let values: Vec<String> = values.iter().map(|v| number(*v)).collect();
+    format!("[{}]", values.join(", "))
+}
+
+fn usage(usage: &Usage) -> String {
+    format!(
+        "{{\"length\": {}, \"volume\": {}, \"weight\": {}, \"cost\": {}}}",
+        number(usage.length),
+        number(usage.volume),
+        number(usage.weight),
+        number(usage.cost),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::tests::load_text;
+
+    #[test]
+    fn slicer_from_prusa_and_cura_comments() {
+        assert_eq!(
+            Slicer::parse("; generated by SuperSlicer 2.4 on 2023-05-01 at 12:00:00 UTC"),
+            Some(Slicer { name: String::from("SuperSlicer"), version: Some(String::from("2.4")) }),
+        );
+        assert_eq!(
+            Slicer::parse(";Generated with Cura_SteamEngine 5.4.0"),
+            Some(Slicer { name: String::from("Cura_SteamEngine"), version: Some(String::from("5.4.0")) }),
+        );
+        // the date isnt a version
+        assert_eq!(
+            Slicer::parse("; generated by OrcaSlicer on 2023-05-01"),
+            Some(Slicer { name: String::from("OrcaSlicer"), version: None }),
+        );
+        assert_eq!(Slicer::parse("; layer_height = 0.2"), None);
+        assert_eq!(Slicer::parse("; generated by"), None);
+    }
+
+    #[test]
+    fn thumbnails_of_every_format() {
+        assert_eq!(
+            Thumbnail::parse("; thumbnail begin 32x32 2548"),
+            Some(Thumbnail { width: 32, height: 32, size: 2548 }),
+        );
+        assert_eq!(
+            Thumbnail::parse("; thumbnail_QOI begin 400x300 51200"),
+            Some(Thumbnail { width: 400, height: 300, size: 51200 }),
+        );
+        assert_eq!(Thumbnail::parse("; thumbnail begin 16x16"), Some(Thumbnail { width: 16, height: 16, size: 0 }));
+        assert_eq!(Thumbnail::parse("; thumbnail end"), None);
+        assert_eq!(Thumbnail::parse("; thumbnail begin 32 2548"), None);
+        assert_eq!(Thumbnail::parse("G1 X10"), None);
+    }
+
+    #[test]
+    fn json_escapes_text_and_drops_what_it_cant_hold() {
+        let (toolpath, source) = load_text("; generated by PrusaSlicer 2.6.0\nG28\nG1 Z0.2\nG1 X10 Y10 E1\n");
+        let mut summary = Summary::new(&toolpath, &source, &[]);
+        let json = summary.to_json();
+        assert!(json.contains(r#""slicer": {"name": "PrusaSlicer", "version": "2.6.0"}"#), "{}", json);
+        assert!(json.starts_with("{\n") && json.ends_with("\n}"));
+
+        summary.slicer = Some(Slicer { name: String::from("say \"hi\" C:\\parts\t"), version: None });
+        summary.planner_time = f32::NAN;
+        let json = summary.to_json();
+        assert!(json.contains(r#""slicer": {"name": "say \"hi\" C:\\parts\u0009", "version": null}"#), "{}", json);
+        assert!(json.contains(r#""planner": null"#), "{}", json);
+    }
+}
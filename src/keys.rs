@@ -0,0 +1,128 @@
+use winit::event::{ModifiersState, VirtualKeyCode};
+
+use crate::hud;
+use crate::text::{self, Anchor};
+
+// everything a key can do. the camera keys arent in here, the camera
+// controller looks after those itself
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Open,
+    Hud,
+    Keys,
+    CameraMode,
+    LayerUp,
+    LayerDown,
+    Source,
+    PreviousMove,
+    NextMove,
+    Playback,
+    PlayPause,
+    StepBack,
+    StepForward,
+    Slower,
+    Faster,
+    Usage,
+    Retractions,
+    Objects,
+    NextObject,
+    HideObject,
+    IsolateObject,
+    Commands,
+    Seams,
+    DiffView,
+    Diff,
+}
+
+pub struct Binding {
+    pub key: VirtualKeyCode,
+    pub ctrl: bool,
+    // how the keys panel shows the key
+    pub name: &'static str,
+    pub action: Action,
+    pub description: &'static str,
+}
+
+const fn bind(key: VirtualKeyCode, name: &'static str, action: Action, description: &'static str) -> Binding {
+    Binding { key, ctrl: false, name, action, description }
+}
+
+// input() looks keys up in here and the keys panel lists it, in this order.
+// anything with ctrl comes first so it wins over the same key without
+pub const BINDINGS: &[Binding] = &[
+    Binding { key: VirtualKeyCode::O, ctrl: true, name: "ctrl+o", action: Action::Open, description: "open another file" },
+    bind(VirtualKeyCode::H, "h", Action::Hud, "info panel"),
+    bind(VirtualKeyCode::K, "k", Action::Keys, "these keys"),
+    bind(VirtualKeyCode::T, "t", Action::CameraMode, "orbit or look down"),
+    bind(VirtualKeyCode::PageUp, "page up", Action::LayerUp, "next layer"),
+    bind(VirtualKeyCode::PageDown, "page down", Action::LayerDown, "previous layer"),
+    bind(VirtualKeyCode::G, "g", Action::Source, "g-code panel"),
+    bind(VirtualKeyCode::Comma, ",", Action::PreviousMove, "previous move in the g-code"),
+    bind(VirtualKeyCode::Period, ".", Action::NextMove, "next move in the g-code"),
+    bind(VirtualKeyCode::P, "p", Action::Playback, "replay the print"),
+    bind(VirtualKeyCode::Space, "space", Action::PlayPause, "pause the replay"),
+    bind(VirtualKeyCode::LBracket, "[", Action::StepBack, "replay a move back"),
+    bind(VirtualKeyCode::RBracket, "]", Action::StepForward, "replay a move on"),
+    bind(VirtualKeyCode::Minus, "-", Action::Slower, "replay slower"),
+    bind(VirtualKeyCode::Equals, "=", Action::Faster, "replay faster"),
+    bind(VirtualKeyCode::F, "f", Action::Usage, "filament used"),
+    bind(VirtualKeyCode::R, "r", Action::Retractions, "retractions"),
+    bind(VirtualKeyCode::B, "b", Action::Objects, "objects"),
+    bind(VirtualKeyCode::Tab, "tab", Action::NextObject, "select the next object"),
+    bind(VirtualKeyCode::X, "x", Action::HideObject, "hide the selected object"),
+    bind(VirtualKeyCode::I, "i", Action::IsolateObject, "show only the selected object"),
+    bind(VirtualKeyCode::M, "m", Action::Commands, "macros and extended commands"),
+    bind(VirtualKeyCode::E, "e", Action::Seams, "all seams, external seams or none"),
+    bind(VirtualKeyCode::V, "v", Action::DiffView, "overlay or side by side, with --compare"),
+    bind(VirtualKeyCode::C, "c", Action::Diff, "differences, with --compare"),
+];
+
+pub fn action(key: VirtualKeyCode, modifiers: ModifiersState) -> Option<Action> {
+    BINDINGS.iter()
+        .find(|binding| binding.key == key && (!binding.ctrl || modifiers.ctrl()))
+        .map(|binding| binding.action)
+}
+
+// every key and what it does, in the bottom left corner on top of the
+// other panels there
+pub struct KeysPanel {
+    pub panel: hud::Panel,
+}
+
+impl KeysPanel {
+    pub fn new(text: &mut text::Layer) -> Self {
+        Self { panel: hud::Panel::new(text, Anchor::BottomLeft) }
+    }
+
+    // call once per frame. the rows never change so theyre only built the
+    // first time its shown
+    pub fn update(&mut self, text: &mut text::Layer) {
+        if self.panel.refresh(None) {
+            self.panel.set_rows(text, &rows());
+        }
+    }
+}
+
+// the camera keys first
+fn rows() -> Vec<(&'static str, String)> {
+    let mut rows = vec![("w a s d", String::from("orbit and zoom, or the arrow keys"))];
+    rows.extend(BINDINGS.iter().map(|binding| (binding.name, String::from(binding.description))));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_key_does_one_thing() {
+        for (i, a) in BINDINGS.iter().enumerate() {
+            for b in &BINDINGS[i + 1..] {
+                assert!(a.key != b.key || a.ctrl != b.ctrl, "{} is bound twice", a.name);
+            }
+        }
+        assert_eq!(action(VirtualKeyCode::O, ModifiersState::CTRL), Some(Action::Open));
+        assert_eq!(action(VirtualKeyCode::O, ModifiersState::empty()), None);
+        assert_eq!(action(VirtualKeyCode::H, ModifiersState::CTRL), Some(Action::Hud));
+    }
+}
@@ -0,0 +1,17 @@
+pub mod reader;
+pub mod toolpath;
+pub mod config;
+pub mod diff;
+pub mod info;
+pub mod lint;
+pub mod loader;
+pub mod macros;
+pub mod objects;
+pub mod planner;
+pub mod retraction;
+pub mod seams;
+pub mod source;
+pub mod stats;
+pub mod tools;
+pub mod volume;
+pub mod watch;
@@ -0,0 +1,613 @@
+use std::fmt;
+
+use crate::macros;
+use crate::source::Source;
+use crate::toolpath::{self, Bounds, Event, Heater, Move, Op, Toolpath};
+use crate::volume;
+
+// marlin refuses to extrude below this (EXTRUDE_MINTEMP), C
+const MIN_EXTRUDE_TEMPERATURE: f32 = 170.0;
+// faster than any printer moves, mm/s
+const MAX_FEEDRATE: f32 = 1000.0;
+// mm of filament in a single move
+const MAX_EXTRUSION: f32 = 50.0;
+// mm of filament per mm of xy movement. slicers stay well under 0.2, a
+// whole mm is a bead bigger than the filament going in
+const MAX_EXTRUSION_RATIO: f32 = 1.0;
+// mm of filament retractions are allowed to be out by
+const RETRACTION_TOLERANCE: f32 = 0.01;
+
+// what klipper configs usually call them. anything the file calls before
+// the print is assumed to home and heat up
+const START_MACROS: [&str; 2] = ["PRINT_START", "START_PRINT"];
+const END_MACROS: [&str; 2] = ["PRINT_END", "END_PRINT"];
+
+// marlin, klipper and prusa firmware between them. not everything they
+// have but anything a slicer or a start script would send
+const KNOWN_G: &[u32] = &[
+    0, 1, 2, 3, 4, 5, 6, 10, 11, 12, 17, 18, 19, 20, 21, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35,
+    38, 42, 53, 54, 55, 56, 57, 58, 59, 60, 61, 76, 80, 90, 91, 92, 425,
+];
+const KNOWN_M: &[u32] = &[
+    0, 1, 3, 4, 5, 7, 8, 9, 10, 11, 16, 17, 18, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31,
+    32, 33, 34, 42, 43, 48, 73, 75, 76, 77, 78, 80, 81, 82, 83, 84, 85, 86, 87, 92, 98, 99, 100,
+    104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 117, 118, 119, 120, 121, 122, 123,
+    125, 126, 127, 128, 129, 140, 141, 143, 145, 149, 150, 154, 155, 163, 164, 165, 166, 190,
+    191, 192, 193, 200, 201, 202, 203, 204, 205, 206, 207, 208, 209, 211, 217, 218, 220, 221,
+    226, 240, 250, 256, 260, 261, 280, 281, 282, 290, 300, 301, 302, 303, 304, 305, 306, 350,
+    351, 355, 360, 361, 362, 363, 364, 380, 381, 400, 401, 402, 403, 404, 405, 406, 407, 410,
+    412, 413, 420, 421, 422, 423, 425, 428, 430, 486, 500, 501, 502, 503, 504, 510, 511, 512,
+    524, 540, 552, 569, 575, 593, 600, 603, 605, 665, 666, 672, 701, 702, 710, 808, 810, 851,
+    860, 861, 862, 871, 876, 890, 900, 906, 907, 908, 909, 910, 911, 912, 913, 914, 915, 916,
+    917, 918, 919, 928, 993, 994, 995, 997, 999,
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warning,
+    // the print will probably fail or hurt the printer
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+// something wrong with the file, at a line in it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    pub line: u32,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.line, self.severity.name(), self.message)
+    }
+}
+
+// everything that could be found, in file order. the source is for the
+// names of commands the toolpath only keeps the line of
+pub fn lint(toolpath: &Toolpath, source: &Source, volume: Option<&Bounds>) -> Vec<Issue> {
+    let mut linter = Linter::new(source);
+    let mut events = toolpath.events.iter().peekable();
+    for (i, m) in toolpath.moves.iter().enumerate() {
+        while let Some(event) = events.next_if(|event| event.before <= i) {
+            linter.event(event);
+        }
+        linter.step(m);
+    }
+    for event in events {
+        linter.event(event);
+    }
+    linter.finish(toolpath);
+
+    let mut issues = linter.issues;
+    if let Some(volume) = volume {
+        outside_volume(toolpath, volume, &mut issues);
+    }
+    // stable, so issues on the same line stay in the order they were found
+    issues.sort_by_key(|issue| issue.line);
+    issues
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Heat {
+    // nothing has said anything about it yet
+    Unset,
+    // target is None when a start macro heated it to who knows what
+    Set { target: Option<f32>, waited: bool },
+}
+
+impl Heat {
+    fn set(&mut self, target: f32, wait: bool) {
+        let waited = match *self {
+            _ if wait => true,
+            // going down or staying put doesnt need waiting for
+            Heat::Set { target: Some(old), waited } if target <= old => waited,
+            _ => false,
+        };
+        *self = Heat::Set { target: Some(target), waited };
+    }
+}
+
+// walks the moves and events in file order, keeping track of what the
+// printer would think is going on
+struct Linter<'a> {
+    source: &'a Source,
+    issues: Vec<Issue>,
+    homed: [bool; 3],
+    hotend: Heat,
+    bed: Heat,
+    relative_extrusion: bool,
+    // where extrusion went absolute again without E being reset, and
+    // whether it was G90 that did it
+    unreset: Option<(u32, bool)>,
+    // mm of filament pulled back and not pushed out again yet
+    retracted: f32,
+    start_macro: Option<u32>,
+    end_macro: Option<u32>,
+    // any klipper style extended command, which is where a start macro
+    // would be expected
+    klipper: bool,
+    // whether anything had homed or heated the printer by the first move,
+    // None until there is one
+    set_up: Option<bool>,
+    // so the one mistake made over and over is only reported once
+    reported_unhomed: bool,
+    reported_unheated: bool,
+    first_extrusion: bool,
+    cold: bool,
+    fast: bool,
+    missing_feedrate: bool,
+}
+
+impl<'a> Linter<'a> {
+    fn new(source: &'a Source) -> Self {
+        Self {
+            source,
+            issues: Vec::new(),
+            homed: [false; 3],
+            hotend: Heat::Unset,
+            bed: Heat::Unset,
+            relative_extrusion: false,
+            unreset: None,
+            retracted: 0.0,
+            start_macro: None,
+            end_macro: None,
+            klipper: false,
+            set_up: None,
+            reported_unhomed: false,
+            reported_unheated: false,
+            first_extrusion: true,
+            cold: false,
+            fast: false,
+            missing_feedrate: false,
+        }
+    }
+
+    fn report(&mut self, line: u32, severity: Severity, message: String) {
+        self.issues.push(Issue { line, severity, message });
+    }
+
+    fn event(&mut self, event: &Event) {
+        match event.op {
+            Op::Home { x, y, z } => {
+                for (homed, axis) in self.homed.iter_mut().zip([x, y, z]) {
+                    *homed |= axis;
+                }
+            },
+            Op::Temperature { heater: Heater::Hotend, target, wait } => self.hotend.set(target, wait),
+            Op::Temperature { heater: Heater::Bed, target, wait } => self.bed.set(target, wait),
+            Op::Absolute | Op::AbsoluteExtrusion => {
+                if self.relative_extrusion {
+                    self.unreset = Some((event.line, matches!(event.op, Op::Absolute)));
+                }
+                self.relative_extrusion = false;
+            },
+            Op::Relative | Op::RelativeExtrusion => {
+                self.relative_extrusion = true;
+                self.unreset = None;
+            },
+            Op::SetPosition { e: Some(_), .. } => self.unreset = None,
+            Op::Code { letter, number } => {
+                let known = match letter {
+                    'G' => KNOWN_G.contains(&number),
+                    'M' => KNOWN_M.contains(&number),
+                    _ => true,
+                };
+                if !known {
+                    let from = if event.expanded { " from a macro" } else { "" };
+                    self.report(event.line, Severity::Warning, format!("unknown command {}{}{}", letter, number, from));
+                }
+            },
+            // the line is the call, not whatever the macro called in turn
+            Op::Extended if !event.expanded => self.extended(event.line),
+            _ => {},
+        }
+    }
+
+    fn extended(&mut self, line: u32) {
+        let Some(text) = self.source.line(line) else {
+            return;
+        };
+        let Some((name, params)) = macros::parse(&text) else {
+            self.report(line, Severity::Warning, format!("unknown command {}", toolpath::strip_comment(&text)));
+            return;
+        };
+        self.klipper = true;
+
+        if START_MACROS.contains(&name.as_str()) {
+            self.start_macro.get_or_insert(line);
+            self.homed = [true; 3];
+            self.hotend = Heat::Set { target: None, waited: true };
+            self.bed = Heat::Set { target: None, waited: true };
+            // the temperatures usually get passed in, as BED=60 EXTRUDER=200
+            // or something like it
+            for (key, value) in params.iter() {
+                let Ok(target) = value.parse() else { continue };
+                if key.contains("BED") {
+                    self.bed = Heat::Set { target: Some(target), waited: true };
+                } else if ["EXTRUDER", "HOTEND", "NOZZLE"].iter().any(|k| key.contains(k)) {
+                    self.hotend = Heat::Set { target: Some(target), waited: true };
+                }
+            }
+        } else if END_MACROS.contains(&name.as_str()) {
+            self.end_macro.get_or_insert(line);
+        }
+    }
+
+    fn step(&mut self, m: &Move) {
+        self.set_up.get_or_insert(self.homed.contains(&true) || self.hotend != Heat::Unset);
+        let moved = [0, 1, 2].map(|axis| m.start[axis] != m.end[axis]);
+        if !self.reported_unhomed && (0..3).any(|axis| moved[axis] && !self.homed[axis]) {
+            self.reported_unhomed = true;
+            let axes: Vec<&str> = ["X", "Y", "Z"]
+                .into_iter()
+                .enumerate()
+                .filter(|&(axis, _)| moved[axis] && !self.homed[axis])
+                .map(|(_, name)| name)
+                .collect();
+            self.report(m.line, Severity::Error, format!("moves {} before homing", axes.join(" ")));
+        }
+
+        if moved.contains(&true) {
+            let speed = m.feedrate / 60.0;
+            if speed <= 0.0 && !self.missing_feedrate {
+                self.missing_feedrate = true;
+                self.report(m.line, Severity::Warning, String::from("moves without a feedrate being set"));
+            }
+            let fast = speed > MAX_FEEDRATE;
+            if fast && !self.fast {
+                self.report(m.line, Severity::Warning, format!("feedrate of {:.0}mm/s", speed));
+            }
+            self.fast = fast;
+        }
+
+        if m.e != 0.0 {
+            if let Some((line, g90)) = self.unreset.take() {
+                let message = if g90 {
+                    "G90 switched extrusion back to absolute without a G92 E, it needs to come before M83"
+                } else {
+                    "extrusion switched to absolute without a G92 E to reset it"
+                };
+                self.report(line, Severity::Warning, String::from(message));
+            }
+        }
+        if m.e > MAX_EXTRUSION {
+            self.report(m.line, Severity::Warning, format!("extrudes {:.1}mm of filament in one move", m.e));
+        } else if m.e < -MAX_EXTRUSION {
+            self.report(m.line, Severity::Warning, format!("retracts {:.1}mm of filament in one move", -m.e));
+        }
+        let xy = ((m.end[0] - m.start[0]).powi(2) + (m.end[1] - m.start[1]).powi(2)).sqrt();
+        if m.is_extrusion() && m.e / xy > MAX_EXTRUSION_RATIO {
+            self.report(m.line, Severity::Warning, format!("extrudes {:.2}mm of filament over {:.2}mm", m.e, xy));
+        }
+
+        if m.e > 0.0 {
+            self.heat(m);
+        }
+        self.retraction(m);
+    }
+
+    fn heat(&mut self, m: &Move) {
+        match self.hotend {
+            Heat::Unset if !self.reported_unheated => {
+                self.reported_unheated = true;
+                self.report(m.line, Severity::Error, String::from("extrudes before the hotend is heated"));
+            },
+            Heat::Set { target: Some(target), .. } if target < MIN_EXTRUDE_TEMPERATURE => {
+                if !self.cold {
+                    self.report(m.line, Severity::Error, format!("cold extrusion, the hotend is set to {:.0}C", target));
+                }
+                self.cold = true;
+                return;
+            },
+            _ => {},
+        }
+        self.cold = false;
+
+        if !std::mem::take(&mut self.first_extrusion) {
+            return;
+        }
+        if let Heat::Set { waited: false, .. } = self.hotend {
+            self.report(m.line, Severity::Warning, String::from("extrudes before the hotend is up to temperature, M104 doesnt wait for it like M109"));
+        }
+        if let Heat::Set { target, waited: false } = self.bed {
+            // S0 is just turning it off
+            if !matches!(target, Some(target) if target <= 0.0) {
+                self.report(m.line, Severity::Warning, String::from("extrudes before the bed is up to temperature, M140 doesnt wait for it like M190"));
+            }
+        }
+    }
+
+    fn retraction(&mut self, m: &Move) {
+        if m.is_extrusion() {
+            if self.retracted > RETRACTION_TOLERANCE {
+                self.report(m.line, Severity::Warning, format!("prints while still retracted by {:.2}mm", self.retracted));
+            }
+            self.retracted = 0.0;
+        } else if m.e < 0.0 {
+            // wipes retract while they move, the same as a plain retraction
+            self.retracted -= m.e;
+        } else if m.e > 0.0 && self.retracted > 0.0 {
+            // pushing filament out with nothing retracted is priming, not
+            // an unretraction
+            self.retracted -= m.e;
+            if self.retracted < -RETRACTION_TOLERANCE {
+                self.report(m.line, Severity::Warning, format!("unretracts {:.2}mm more than was retracted", -self.retracted));
+            }
+            self.retracted = self.retracted.max(0.0);
+        }
+    }
+
+    // a start macro without an end one leaves the heaters on. without a
+    // start macro nothing was assumed about homing or heating, so its only
+    // missing if the file doesnt do those itself, or uses klipper commands
+    // everywhere else
+    fn finish(&mut self, toolpath: &Toolpath) {
+        let first = toolpath.moves.first().map_or(1, |m| m.line);
+        let set_up = self.set_up.unwrap_or(true);
+        match (self.start_macro, self.end_macro) {
+            (Some(_), None) => {
+                let last = self.source.len().max(1);
+                self.report(last, Severity::Warning, format!("calls {} but never {}", START_MACROS[0], END_MACROS[0]));
+            },
+            (None, Some(_)) if !set_up => {
+                let message = format!("calls {} but never {}, and nothing else homes or heats the printer", END_MACROS[0], START_MACROS[0]);
+                self.report(first, Severity::Warning, message);
+            },
+            (None, None) if !set_up => {
+                let message = format!("never calls {} or homes or heats the printer itself", START_MACROS[0]);
+                self.report(first, Severity::Warning, message);
+            },
+            (None, None) if self.klipper => {
+                let message = format!("uses klipper commands but never calls {} or {}", START_MACROS[0], END_MACROS[0]);
+                self.report(first, Severity::Warning, message);
+            },
+            _ => {},
+        }
+    }
+}
+
+// one issue per run of moves outside the volume, so a whole object off the
+// side of the bed doesnt make thousands
+fn outside_volume(toolpath: &Toolpath, volume: &Bounds, issues: &mut Vec<Issue>) {
+    let outside = volume::check(toolpath, volume).outside;
+    let mut runs = outside.iter().peekable();
+    while let Some(&first) = runs.next() {
+        let mut last = first;
+        while let Some(&&next) = runs.peek() {
+            if next != last + 1 {
+                break;
+            }
+            last = next;
+            runs.next();
+        }
+
+        let (start, end) = (&toolpath.moves[first], &toolpath.moves[last]);
+        let lines = if first == last {
+            String::from("move goes")
+        } else {
+            format!("{} moves up to line {} go", last - first + 1, end.line)
+        };
+        let point = if volume::contains(volume, start.end) { start.start } else { start.end };
+        issues.push(Issue {
+            line: start.line,
+            severity: Severity::Error,
+            message: format!(
+                "{} outside the build volume, to {:.2}, {:.2}, {:.2}",
+                lines, point[0], point[1], point[2],
+            ),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::tests::{load_text, load_text_with};
+    use crate::macros::Macros;
+
+    const PRINTER_CFG: &str = "\
+[gcode_macro PRINT_START]
+gcode:
+    {% if printer.toolhead.homed_axes != \"xyz\" %}
+    G28
+    {% endif %}
+    M190 S{params.BED}
+    M109 S{params.EXTRUDER}
+
+[gcode_macro PRINT_END]
+gcode:
+    M104 S0
+    M140 S0
+";
+
+    fn issues_with(text: &str, macros: &Macros) -> Vec<(u32, String)> {
+        let (toolpath, source) = load_text_with(text, macros);
+        lint(&toolpath, &source, None).into_iter().map(|issue| (issue.line, issue.message)).collect()
+    }
+
+    fn issues(text: &str) -> Vec<(u32, String)> {
+        issues_with(text, &Macros::default())
+    }
+
+    fn issue(line: u32, message: &str) -> (u32, String) {
+        (line, String::from(message))
+    }
+
+    // homed, heated and waited for, extruding relative
+    const SET_UP: &str = "G28\nM190 S60\nM109 S210\nM83\n";
+
+    // the issues past the set up, by line within the text given
+    fn after_set_up(text: &str) -> Vec<(u32, String)> {
+        let set_up = SET_UP.lines().count() as u32;
+        issues(&format!("{}{}", SET_UP, text))
+            .into_iter()
+            .map(|(line, message)| (line - set_up, message))
+            .collect()
+    }
+
+    #[test]
+    fn set_up_file_has_no_issues() {
+        assert_eq!(after_set_up("G1 X10 Y10 F3000\nG1 X20 Y10 E1\n"), Vec::new());
+    }
+
+    #[test]
+    fn unknown_commands() {
+        let text = "G1 X10 Y10 F3000\nG7\nM9999\nG1 X20 Y10 E1\n";
+        assert_eq!(after_set_up(text), vec![issue(2, "unknown command G7"), issue(3, "unknown command M9999")]);
+    }
+
+    #[test]
+    fn moves_before_homing_once() {
+        let text = "M190 S60\nM109 S210\nG28 X\nG1 X10 Y10 Z1 F3000\nG1 X20 Y20\n";
+        assert_eq!(issues(text), vec![issue(4, "moves Y Z before homing")]);
+    }
+
+    #[test]
+    fn feedrates_missing_and_too_fast() {
+        assert_eq!(after_set_up("G1 X10 Y10\nG1 X20 Y10 F3000\n"), vec![issue(1, "moves without a feedrate being set")]);
+        // once per run of fast moves
+        let text = "G1 X10 Y10 F120000\nG1 X20 Y10\nG1 X30 Y10 F3000\nG1 X40 Y10 F90000\n";
+        assert_eq!(after_set_up(text), vec![issue(1, "feedrate of 2000mm/s"), issue(4, "feedrate of 1500mm/s")]);
+    }
+
+    #[test]
+    fn absolute_extrusion_without_resetting_e() {
+        let text = "G1 X10 Y10 F3000\nG1 X20 Y10 E1\nG90\nG1 X30 Y10 E2\n";
+        assert_eq!(
+            after_set_up(text),
+            vec![issue(3, "G90 switched extrusion back to absolute without a G92 E, it needs to come before M83")],
+        );
+        let text = "G1 X10 Y10 F3000\nG1 X20 Y10 E1\nM82\nG1 X30 Y10 E2\n";
+        assert_eq!(after_set_up(text), vec![issue(3, "extrusion switched to absolute without a G92 E to reset it")]);
+        let text = "G1 X10 Y10 F3000\nG1 X20 Y10 E1\nM82\nG92 E0\nG1 X30 Y10 E1\n";
+        assert_eq!(after_set_up(text), Vec::new());
+    }
+
+    #[test]
+    fn too_much_filament() {
+        let text = "G1 X10 Y10 F3000\nG1 E-60\nG1 E60\nG1 X20 Y10 E15\n";
+        assert_eq!(
+            after_set_up(text),
+            vec![
+                issue(2, "retracts 60.0mm of filament in one move"),
+                issue(3, "extrudes 60.0mm of filament in one move"),
+                issue(4, "extrudes 15.00mm of filament over 10.00mm"),
+            ],
+        );
+    }
+
+    #[test]
+    fn extruding_cold_or_before_heating() {
+        let text = "G28\nG1 X10 Y10 F3000\nG1 X20 Y10 E1\nG1 X30 Y10 E1\n";
+        assert_eq!(issues(text), vec![issue(3, "extrudes before the hotend is heated")]);
+
+        let text = "M109 S150\nG1 X10 Y10 F3000\nG1 X20 Y10 E1\nG1 X30 Y10 E1\nM109 S210\nG1 X40 Y10 E1\n";
+        assert_eq!(after_set_up(text), vec![issue(3, "cold extrusion, the hotend is set to 150C")]);
+    }
+
+    #[test]
+    fn extruding_before_waiting_for_heaters() {
+        let text = "G28\nM140 S60\nM104 S210\nM83\nG1 X10 Y10 F3000\nG1 X20 Y10 E1\n";
+        assert_eq!(
+            issues(text),
+            vec![
+                issue(6, "extrudes before the hotend is up to temperature, M104 doesnt wait for it like M109"),
+                issue(6, "extrudes before the bed is up to temperature, M140 doesnt wait for it like M190"),
+            ],
+        );
+        // turning the bed off, or the hotend down, doesnt need waiting for
+        let text = "G28\nM140 S0\nM109 S215\nM104 S210\nM83\nG1 X10 Y10 F3000\nG1 X20 Y10 E1\n";
+        assert_eq!(issues(text), Vec::new());
+    }
+
+    #[test]
+    fn retractions_that_dont_add_up() {
+        let text = "G1 X10 Y10 F3000\nG1 E-0.8\nG1 X20 Y10 E1\n";
+        assert_eq!(after_set_up(text), vec![issue(3, "prints while still retracted by 0.80mm")]);
+        let text = "G1 X10 Y10 F3000\nG1 E-0.8\nG1 E1.3\nG1 X20 Y10 E1\n";
+        assert_eq!(after_set_up(text), vec![issue(3, "unretracts 0.50mm more than was retracted")]);
+        // a wipe retracts while it moves, and priming isnt unretracting
+        let text = "G1 X10 Y10 F3000\nG1 E5\nG1 X20 Y10 E1\nG1 X15 Y10 E-0.4\nG1 E0.4\nG1 X25 Y10 E1\n";
+        assert_eq!(after_set_up(text), Vec::new());
+    }
+
+    #[test]
+    fn start_macro_heats_to_what_it_was_passed() {
+        let text = "PRINT_START BED=60 EXTRUDER=150\nG1 X10 Y10 F3000\nG1 X20 Y10 E1\nPRINT_END\n";
+        assert_eq!(issues(text), vec![issue(3, "cold extrusion, the hotend is set to 150C")]);
+    }
+
+    #[test]
+    fn expanding_macros_keeps_the_start_and_end_calls() {
+        let text = "PRINT_START BED=60 EXTRUDER=210\nG1 X10 Y10 F3000\nG1 X20 Y10 E1\nPRINT_END\n";
+        assert_eq!(issues(text), Vec::new());
+        assert_eq!(issues_with(text, &Macros::parse(PRINTER_CFG)), Vec::new());
+    }
+
+    #[test]
+    fn start_macro_without_end() {
+        let text = "PRINT_START BED=60 EXTRUDER=210\nG1 X10 Y10 F3000\nG1 X20 Y10 E1\n";
+        assert_eq!(issues(text), vec![issue(3, "calls PRINT_START but never PRINT_END")]);
+    }
+
+    #[test]
+    fn end_macro_without_start_is_fine_if_the_file_sets_up_itself() {
+        let text = "G28\nM190 S60\nM109 S210\nG1 X10 Y10 F3000\nG1 X20 Y10 E1\nPRINT_END\n";
+        assert_eq!(issues(text), Vec::new());
+    }
+
+    #[test]
+    fn end_macro_without_start_or_set_up() {
+        let text = "G1 X10 Y10 F3000\nPRINT_END\n";
+        assert_eq!(
+            issues(text).last(),
+            Some(&issue(1, "calls PRINT_END but never PRINT_START, and nothing else homes or heats the printer")),
+        );
+    }
+
+    #[test]
+    fn no_macros_and_no_set_up() {
+        let text = "G1 X10 Y10 F3000\nG1 X20 Y10 E1\n";
+        assert!(issues(text).contains(&issue(1, "never calls PRINT_START or homes or heats the printer itself")));
+    }
+
+    #[test]
+    fn no_macros_in_a_klipper_file() {
+        let text = "G28\nM190 S60\nM109 S210\nSET_FAN_SPEED FAN=part SPEED=1\nG1 X10 Y10 F3000\nG1 X20 Y10 E1\n";
+        assert_eq!(issues(text), vec![issue(5, "uses klipper commands but never calls PRINT_START or PRINT_END")]);
+    }
+
+    #[test]
+    fn no_macros_in_a_plain_file() {
+        let text = "G28\nM190 S60\nM109 S210\nG1 X10 Y10 F3000\nG1 X20 Y10 E1\n";
+        assert_eq!(issues(text), Vec::new());
+    }
+
+    #[test]
+    fn one_issue_per_run_outside_the_volume() {
+        let text = "G1 X10 Y10 F3000\nG1 X310 Y10 E1\nG1 X320 Y10 E1\nG1 X10 Y10\nG1 X20 Y10\nG1 X20 Y10 Z300\n";
+        let (toolpath, source) = load_text(&format!("{}{}", SET_UP, text));
+        let volume = Bounds { min: [0.0; 3], max: [300.0, 300.0, 250.0] };
+        let issues: Vec<(u32, String)> = lint(&toolpath, &source, Some(&volume))
+            .into_iter()
+            .map(|issue| (issue.line, issue.message))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                issue(6, "3 moves up to line 8 go outside the build volume, to 310.00, 10.00, 0.00"),
+                issue(10, "move goes outside the build volume, to 20.00, 10.00, 300.00"),
+            ],
+        );
+    }
+}
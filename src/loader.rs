@@ -0,0 +1,238 @@
+use std::{
+    io,
+    path::Path,
+};
+
+use rayon::prelude::*;
+
+use crate::config::Config;
+use crate::macros::Macros;
+use crate::reader::BufferedReader;
+use crate::source::Source;
+use crate::toolpath::{self, MachineState, Op, Toolpath};
+
+// chunks smaller than this arent worth handing to another thread
+const MIN_CHUNK_SIZE: usize = 256 * 1024;
+
+// the original line by line loader. still around so the bench has
+// something to compare against
+pub fn load_buffered(path: impl AsRef<Path>) -> io::Result<Toolpath> {
+    let mut reader = BufferedReader::open(path)?;
+    let mut buffer = String::new();
+
+    let mut toolpath = Toolpath::new();
+    let mut state = MachineState::new();
+    let mut line_number = 0;
+
+    while let Some(line) = reader.read_line(&mut buffer) {
+        line_number += 1;
+        if let Some(op) = toolpath::lex_line(line.as_str()) {
+            toolpath.push(line_number, op, &mut state);
+        } else if let Some((key, value)) = Config::parse_line(line.as_str()) {
+            toolpath.config.insert(key, value);
+        }
+    }
+
+    Ok(toolpath)
+}
+
+// memory maps the file and lexes it, along with the text it came from for
+// anything that needs to show lines of it. both share the one mapping.
+// calls to any of the macros are followed by the gcode they would run
+pub fn open(path: impl AsRef<Path>, macros: &Macros) -> io::Result<(Toolpath, Source)> {
+    let source = Source::open(path)?;
+    let toolpath = load_bytes_with(source.bytes(), macros);
+    Ok((toolpath, source))
+}
+
+pub fn load_bytes(bytes: &[u8]) -> Toolpath {
+    load_bytes_with(bytes, &Macros::default())
+}
+
+// splits the text at line boundaries and lexes the chunks in parallel.
+// lexing is the expensive part, replaying the ops through the machine
+// state afterwards is just a bit of arithmetic per line
+pub fn load_bytes_with(bytes: &[u8], macros: &Macros) -> Toolpath {
+    let threads = rayon::current_num_threads().max(1);
+    let chunk_size = (bytes.len() / threads).max(MIN_CHUNK_SIZE);
+
+    let lexed: Vec<Chunk> = split_lines(bytes, chunk_size)
+        .par_iter()
+        .map(|chunk| lex_chunk(chunk, macros))
+        .collect();
+
+    stitch(lexed)
+}
+
+// ops for one chunk, keyed by their line offset within the chunk and
+// whether they came out of a macro
+struct Chunk {
+    ops: Vec<(u32, Op, bool)>,
+    settings: Vec<(String, String)>,
+    lines: u32,
+}
+
+fn split_lines(bytes: &[u8], chunk_size: usize) -> Vec<&[u8]> {
+    let mut chunks = Vec::new();
+    let mut rest = bytes;
+    while rest.len() > chunk_size {
+        // push the split point forward to the end of the current line
+        let split = match rest[chunk_size..].iter().position(|b| *b == b'\n') {
+            Some(i) => chunk_size + i + 1,
+            None => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(split);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+fn lex_chunk(chunk: &[u8], macros: &Macros) -> Chunk {
+    let mut ops = Vec::new();
+    let mut settings = Vec::new();
+    let mut lines = 0;
+    for line in chunk.split(|b| *b == b'\n') {
+        lines += 1;
+        // anything that isnt utf8 is going to be in a comment or a
+        // thumbnail anyway
+        let Ok(line) = std::str::from_utf8(line) else { continue };
+        if let Some(op) = toolpath::lex_line(line) {
+            ops.push((lines, op, false));
+            // the call stays an event of its own, and everything the macro
+            // does happens right after it on the same line
+            if matches!(op, Op::Extended) && !macros.is_empty() {
+                if let Some(expanded) = macros.expand(line) {
+                    ops.extend(expanded.iter().filter_map(|line| toolpath::lex_line(line)).map(|op| (lines, op, true)));
+                }
+            }
+        } else if let Some((key, value)) = Config::parse_line(line) {
+            settings.push((String::from(key), String::from(value)));
+        }
+    }
+    // a chunk ending in a newline doesnt start another line
+    if chunk.last() == Some(&b'\n') {
+        lines -= 1;
+    }
+    Chunk { ops, settings, lines }
+}
+
+// replays every chunk in file order, handing the machine state (position,
+// E and the absolute/relative modes) from the end of one chunk to the start
+// of the next
+fn stitch(chunks: Vec<Chunk>) -> Toolpath {
+    let mut toolpath = Toolpath::new();
+    toolpath.moves.reserve(chunks.iter().map(|c| c.ops.len()).sum());
+
+    let mut state = MachineState::new();
+    let mut first_line = 0;
+    for chunk in chunks {
+        for (offset, op, expanded) in chunk.ops {
+            if expanded {
+                toolpath.push_expanded(first_line + offset, op, &mut state);
+            } else {
+                toolpath.push(first_line + offset, op, &mut state);
+            }
+        }
+        for (key, value) in chunk.settings.iter() {
+            toolpath.config.insert(key, value);
+        }
+        first_line += chunk.lines;
+    }
+
+    toolpath
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::source::Source;
+
+    // the toolpath and the source for some gcode, for the tests of anything
+    // that needs both
+    pub fn load_text(text: &str) -> (Toolpath, Source) {
+        load_text_with(text, &Macros::default())
+    }
+
+    pub fn load_text_with(text: &str, macros: &Macros) -> (Toolpath, Source) {
+        (load_bytes_with(text.as_bytes(), macros), Source::from_bytes(text.as_bytes().to_vec()))
+    }
+
+    // absolute and relative moves, a reset of E, comments, a blank line,
+    // settings at the end and no newline after the last one
+    const GCODE: &str = "\
+; generated by PrusaSlicer 2.6.0
+G28
+M83
+G1 Z0.2 F3000
+G1 X10 Y10 E1.5 F1500
+
+G1 X20 Y10 E0.8 ; perimeter
+G91
+G1 X5 Y5 E0.4
+G1 Z0.2
+G90
+M82
+G92 E0
+G1 X30 Y30 E2.0
+G1 X40 Y30 E3.5
+G10
+G0 X0 Y0
+; layer_height = 0.2
+; nozzle_diameter = 0.4";
+
+    // everything the two loaders should agree on. Move and Event dont
+    // compare, their debug output does
+    fn summary(toolpath: &Toolpath) -> (String, String, Config) {
+        (format!("{:?}", toolpath.moves), format!("{:?}", toolpath.events), toolpath.config.clone())
+    }
+
+    fn load_chunked(bytes: &[u8], chunk_size: usize) -> Toolpath {
+        stitch(split_lines(bytes, chunk_size).iter().map(|chunk| lex_chunk(chunk, &Macros::default())).collect())
+    }
+
+    #[test]
+    fn split_lines_only_splits_after_a_newline() {
+        let bytes = GCODE.as_bytes();
+        for chunk_size in 1..bytes.len() {
+            let chunks = split_lines(bytes, chunk_size);
+            assert_eq!(chunks.concat(), bytes);
+            for chunk in chunks[..chunks.len() - 1].iter() {
+                assert_eq!(chunk.last(), Some(&b'\n'));
+            }
+        }
+    }
+
+    #[test]
+    fn every_chunk_size_loads_the_same_toolpath() {
+        let bytes = GCODE.as_bytes();
+        let whole = summary(&load_bytes(bytes));
+        for chunk_size in 1..bytes.len() {
+            assert_eq!(summary(&load_chunked(bytes, chunk_size)), whole, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn open_matches_load_buffered() {
+        let path = std::env::temp_dir().join(format!("fig-loader-test-{}.gcode", std::process::id()));
+        std::fs::write(&path, GCODE).unwrap();
+        let buffered = load_buffered(&path).unwrap();
+        let (toolpath, source) = open(&path, &Macros::default()).unwrap();
+        let line = source.line(4).map(String::from);
+        // the map has to go before the file can on some platforms
+        drop(source);
+        std::fs::write(&path, "").unwrap();
+        let empty = open(&path, &Macros::default()).map(|(toolpath, source)| (toolpath.moves.len(), source.len()));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(summary(&toolpath), summary(&buffered));
+        assert_eq!(buffered.config.get("nozzle_diameter"), Some("0.4"));
+        // the line numbers start at one
+        assert_eq!(buffered.moves.first().map(|m| m.line), Some(4));
+        assert_eq!(line.as_deref(), Some("G1 Z0.2 F3000"));
+        assert_eq!(empty.unwrap(), (0, 0));
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::Path,
+};
+
+use crate::source::Source;
+use crate::toolpath::{self, Op, Toolpath};
+
+// macros calling macros calling macros, past this its probably a loop
+const MAX_DEPTH: usize = 8;
+
+// commands klipper has built in (or that come with its standard modules),
+// as opposed to macros someone wrote in their printer.cfg
+const BUILTINS: &[&str] = &[
+    "ACTIVATE_EXTRUDER", "BED_MESH_CALIBRATE", "BED_MESH_CLEAR", "BED_MESH_OFFSET", "BED_MESH_PROFILE",
+    "EXCLUDE_OBJECT", "EXCLUDE_OBJECT_DEFINE", "EXCLUDE_OBJECT_END", "EXCLUDE_OBJECT_START",
+    "FORCE_MOVE", "QUAD_GANTRY_LEVEL", "RESPOND", "RESTORE_GCODE_STATE", "SAVE_GCODE_STATE",
+    "SET_DISPLAY_TEXT", "SET_EXTRUDER_ROTATION_DISTANCE", "SET_FAN_SPEED", "SET_GCODE_OFFSET",
+    "SET_HEATER_TEMPERATURE", "SET_IDLE_TIMEOUT", "SET_INPUT_SHAPER", "SET_KINEMATIC_POSITION",
+    "SET_LED", "SET_PIN", "SET_PRESSURE_ADVANCE", "SET_PRINT_STATS_INFO", "SET_RETRACTION",
+    "SET_SERVO", "SET_STEPPER_ENABLE", "SET_TMC_CURRENT", "SET_VELOCITY_LIMIT", "SYNC_EXTRUDER_MOTION",
+    "TEMPERATURE_WAIT", "Z_TILT_ADJUST",
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    // one of BUILTINS
+    Builtin,
+    // anything else with a klipper style name, PRINT_START and friends
+    Macro,
+    // reprap firmwares M98 P"file.g", running another gcode file
+    Call,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Builtin => "builtin",
+            Kind::Macro => "macro",
+            Kind::Call => "call",
+        }
+    }
+}
+
+// an extended command pulled back out of the source, since the op for it
+// doesnt carry any text
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    // upper case, or the file for a call
+    pub name: String,
+    // KEY=value, keys upper case
+    pub params: Vec<(String, String)>,
+    pub kind: Kind,
+    pub line: u32,
+    // the index of the move that comes after it
+    pub before: usize,
+}
+
+// PRINT_START BED=60 EXTRUDER=200 gives the name and the params. anything
+// that doesnt start with a name made of letters, digits and underscores
+// isnt one
+pub fn parse(code: &str) -> Option<(String, Vec<(String, String)>)> {
+    let code = toolpath::strip_comment(code);
+    let name = code.split_whitespace().next()?.to_ascii_uppercase();
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    let params = toolpath::params(code).map(|(key, value)| (key, String::from(value))).collect();
+    Some((name, params))
+}
+
+// every extended command and M98 in the file, in order. whatever the
+// loader expanded them into isnt in the file, so it isnt listed
+pub fn commands(toolpath: &Toolpath, source: &Source) -> Vec<Command> {
+    let mut commands = Vec::new();
+    for event in toolpath.events.iter().filter(|event| !event.expanded) {
+        let is_call = matches!(event.op, Op::Code { letter: 'M', number: 98 });
+        if !is_call && !matches!(event.op, Op::Extended) {
+            continue;
+        }
+        let Some(line) = source.line(event.line) else { continue };
+        let code = toolpath::strip_comment(&line);
+        let command = if is_call {
+            // M98 P"0:/macros/purge.g", the quotes are optional
+            let file = code
+                .split_whitespace()
+                .skip(1)
+                .find_map(|word| word.strip_prefix('P').or_else(|| word.strip_prefix('p')))
+                .unwrap_or_default()
+                .trim_matches('"');
+            Command {
+                name: String::from(file),
+                params: Vec::new(),
+                kind: Kind::Call,
+                line: event.line,
+                before: event.before,
+            }
+        } else {
+            let Some((name, params)) = parse(code) else { continue };
+            let kind = if BUILTINS.contains(&name.as_str()) { Kind::Builtin } else { Kind::Macro };
+            Command { name, params, kind, line: event.line, before: event.before }
+        };
+        commands.push(command);
+    }
+    commands
+}
+
+// [gcode_macro NAME] sections from a klipper config. with none loaded
+// every macro is opaque, an event and nothing else. with one loaded the
+// loader follows each call with whatever gcode it runs
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Macros {
+    // upper case name to the lines of its gcode: template
+    macros: HashMap<String, Vec<String>>,
+}
+
+impl Macros {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut macros = HashMap::new();
+        let mut name: Option<String> = None;
+        let mut body: Option<Vec<String>> = None;
+        for line in text.lines() {
+            let indented = line.starts_with([' ', '\t']);
+            let trimmed = line.trim();
+            if let Some(lines) = body.as_mut() {
+                if indented || trimmed.is_empty() {
+                    if !trimmed.is_empty() {
+                        lines.push(String::from(trimmed));
+                    }
+                    continue;
+                }
+                if let (Some(name), Some(lines)) = (name.clone(), body.take()) {
+                    macros.insert(name, lines);
+                }
+            }
+            if trimmed.starts_with('#') || trimmed.starts_with(';') {
+                continue;
+            }
+            if let Some(section) = trimmed.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                name = section
+                    .strip_prefix("gcode_macro")
+                    .map(|name| name.trim().to_ascii_uppercase())
+                    .filter(|name| !name.is_empty());
+            } else if let Some(first) = trimmed.strip_prefix("gcode:").filter(|_| name.is_some()) {
+                let first = first.trim();
+                body = Some(if first.is_empty() { Vec::new() } else { vec![String::from(first)] });
+            }
+        }
+        if let (Some(name), Some(lines)) = (name, body) {
+            macros.insert(name, lines);
+        }
+        Self { macros }
+    }
+
+    pub fn len(&self) -> usize {
+        self.macros.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.macros.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.macros.contains_key(&name.to_ascii_uppercase())
+    }
+
+    // the gcode a call runs, with its params filled in and any macros it
+    // calls expanded too. None if its not one of ours
+    pub fn expand(&self, code: &str) -> Option<Vec<String>> {
+        self.expand_depth(code, 0)
+    }
+
+    fn expand_depth(&self, code: &str, depth: usize) -> Option<Vec<String>> {
+        let (name, params) = parse(code)?;
+        let body = self.macros.get(&name)?;
+        if depth >= MAX_DEPTH {
+            return None;
+        }
+        let mut lines = Vec::new();
+        for line in render(body, &params.into_iter().collect()) {
+            match self.expand_depth(&line, depth + 1) {
+                Some(expanded) => lines.extend(expanded),
+                None => lines.push(line),
+            }
+        }
+        Some(lines)
+    }
+}
+
+// just enough jinja for the usual start and end macros: {% set %}s and
+// {}s made out of params, numbers and the |default, |int and |float
+// filters. if and for blocks arent run, everything inside one is left out
+// rather than guessing, and so is any line using something else (like the
+// printer object) since theres no printer to ask
+fn render(body: &[String], params: &HashMap<String, String>) -> Vec<String> {
+    let mut variables: HashMap<String, String> = HashMap::new();
+    let mut blocks = 0usize;
+    let mut lines = Vec::new();
+    for line in body.iter() {
+        if let Some(statement) = line.strip_prefix("{%").and_then(|s| s.strip_suffix("%}")) {
+            let statement = statement.trim_matches(|c: char| c == '-' || c.is_whitespace());
+            let keyword = statement.split_whitespace().next().unwrap_or_default();
+            match keyword {
+                "if" | "for" => blocks += 1,
+                "endif" | "endfor" => blocks = blocks.saturating_sub(1),
+                "set" if blocks == 0 => {
+                    let Some((name, expression)) = statement["set".len()..].split_once('=') else { continue };
+                    if let Some(value) = evaluate(expression, params, &variables) {
+                        variables.insert(String::from(name.trim()), value);
+                    }
+                },
+                _ => {},
+            }
+            continue;
+        }
+        if blocks > 0 || line.starts_with('#') {
+            continue;
+        }
+        // comments go before substituting, a {} in one shouldnt lose the
+        // command in front of it. lines that were only a comment go too
+        let line = line.split(';').next().unwrap_or_default().trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(line) = substitute(line, params, &variables) {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+fn substitute(line: &str, params: &HashMap<String, String>, variables: &HashMap<String, String>) -> Option<String> {
+    let mut output = String::new();
+    let mut rest = line;
+    while let Some(open) = rest.find('{') {
+        output.push_str(&rest[..open]);
+        let close = rest[open..].find('}')? + open;
+        output.push_str(&evaluate(&rest[open + 1..close], params, variables)?);
+        rest = &rest[close + 1..];
+    }
+    output.push_str(rest);
+    Some(output)
+}
+
+fn evaluate(expression: &str, params: &HashMap<String, String>, variables: &HashMap<String, String>) -> Option<String> {
+    let mut parts = expression.split('|');
+    let base = parts.next()?.trim();
+    let mut value = if let Some(key) = base.strip_prefix("params.") {
+        params.get(&key.to_ascii_uppercase()).cloned()
+    } else if let Some(value) = variables.get(base) {
+        Some(value.clone())
+    } else if base.parse::<f32>().is_ok() {
+        Some(String::from(base))
+    } else {
+        base.strip_prefix(['"', '\'']).and_then(|s| s.strip_suffix(['"', '\''])).map(String::from)
+    };
+    for filter in parts {
+        let filter = filter.trim();
+        if let Some(default) = filter.strip_prefix("default(").and_then(|s| s.strip_suffix(')')) {
+            if value.is_none() {
+                value = evaluate(default, params, variables);
+            }
+        } else if filter == "int" {
+            value = value.and_then(|v| v.parse::<f32>().ok()).map(|v| (v as i64).to_string());
+        } else if filter == "float" {
+            value = value.and_then(|v| v.parse::<f32>().ok()).map(|v| v.to_string());
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::tests::load_text_with;
+
+    #[test]
+    fn commands_leave_out_what_a_macro_expanded_into() {
+        let macros = Macros::parse("[gcode_macro PRINT_START]\ngcode:\n    G28\n    BED_MESH_CALIBRATE\n");
+        let text = "PRINT_START\nG1 X10 Y10 F3000\nSET_FAN_SPEED FAN=part SPEED=1\n";
+        let names = |macros: &Macros| -> Vec<(String, u32)> {
+            let (toolpath, source) = load_text_with(text, macros);
+            commands(&toolpath, &source).into_iter().map(|command| (command.name, command.line)).collect()
+        };
+        let expected = vec![(String::from("PRINT_START"), 1), (String::from("SET_FAN_SPEED"), 3)];
+        assert_eq!(names(&Macros::default()), expected);
+        assert_eq!(names(&macros), expected);
+    }
+
+    const PRINTER_CFG: &str = "\
+# the printer
+[printer]
+kinematics: corexy
+
+[gcode_macro print_start]
+description: heats and homes
+gcode:
+    {% set bed = params.BED|default(60)|int %}
+    {% set extruder = params.EXTRUDER|default(200)|float %}
+    M190 S{bed}
+
+    {% if printer.toolhead.homed_axes != \"xyz\" %}
+    G28
+    {% endif %}
+    M109 S{extruder}
+    M117 {printer.toolhead.position}
+    PURGE LENGTH={params.PURGE|default(30)}
+[gcode_macro PURGE]
+gcode: G92 E0
+  G1 X0 Y0 E{params.LENGTH} ; line it up
+[gcode_macro LOOP]
+gcode:
+    M117 again
+    LOOP
+[gcode_macro]
+gcode:
+    G28
+";
+
+    fn lines(lines: &[&str]) -> Option<Vec<String>> {
+        Some(lines.iter().map(|line| String::from(*line)).collect())
+    }
+
+    #[test]
+    fn parse_finds_each_gcode_macro_section() {
+        let macros = Macros::parse(PRINTER_CFG);
+        assert_eq!(macros.len(), 3);
+        assert!(macros.contains("PRINT_START") && macros.contains("print_start"));
+        assert!(macros.contains("PURGE") && macros.contains("LOOP"));
+        assert!(!macros.contains("PRINTER"));
+        assert!(Macros::parse("").is_empty());
+    }
+
+    #[test]
+    fn expand_fills_in_params_and_leaves_out_blocks() {
+        let macros = Macros::parse(PRINTER_CFG);
+        assert_eq!(
+            macros.expand("PRINT_START BED=65.5 EXTRUDER=215 ; go"),
+            lines(&["M190 S65", "M109 S215", "G92 E0", "G1 X0 Y0 E30"]),
+        );
+        // params are case insensitive, and the defaults are used without them
+        assert_eq!(
+            macros.expand("print_start purge=12"),
+            lines(&["M190 S60", "M109 S200", "G92 E0", "G1 X0 Y0 E12"]),
+        );
+        assert_eq!(macros.expand("PURGE"), lines(&["G92 E0"]));
+        assert_eq!(macros.expand("PRINT_END"), None);
+        assert_eq!(macros.expand("G1 X10"), None);
+    }
+
+    #[test]
+    fn expand_drops_comments_before_substituting() {
+        let macros = Macros::parse("[gcode_macro HOME]\ngcode:\n    ; park first\n    G1 X0 ; {printer.toolhead}\n    G28 ; {params.AXES}\n");
+        assert_eq!(macros.expand("HOME"), lines(&["G1 X0", "G28"]));
+    }
+
+    #[test]
+    fn expand_stops_a_macro_calling_itself() {
+        let macros = Macros::parse(PRINTER_CFG);
+        let mut expected = vec!["M117 again"; MAX_DEPTH];
+        expected.push("LOOP");
+        assert_eq!(macros.expand("LOOP"), lines(&expected));
+    }
+
+    #[test]
+    fn parse_splits_names_and_params() {
+        assert_eq!(
+            parse("print_start BED=60 extruder=210 ; start"),
+            Some((
+                String::from("PRINT_START"),
+                vec![(String::from("BED"), String::from("60")), (String::from("EXTRUDER"), String::from("210"))],
+            )),
+        );
+        assert_eq!(parse("@pause"), None);
+        assert_eq!(parse("; just a comment"), None);
+    }
+}
@@ -1,24 +1,180 @@
-mod reader;
 mod camera;
+mod commands;
+mod compare;
+mod font;
+mod hud;
+mod keys;
+mod markers;
+mod outlines;
+mod panel;
+mod pick;
+mod playback;
 mod text;
-mod winfont;
+mod usage;
+
+use std::path::{Path, PathBuf};
 
 use graphics::data::Vertex;
 use wgpu::util::DeviceExt;
+use winit::event::{WindowEvent, KeyboardInput, ElementState, ModifiersState, MouseScrollDelta, MouseButton};
 
-use gcode::GCommand;
+use fig::diff::{Diff, Side};
+use fig::macros::{self, Command, Macros};
+use fig::objects::{self, Objects};
+use fig::planner::{self, Estimate};
+use fig::retraction::{self, Analysis};
+use fig::seams::{self, Seams};
+use fig::tools;
+use fig::source::Source;
+use fig::stats::{self, Stats};
+use fig::toolpath::{Bounds, Layer, Toolpath};
+use fig::volume;
+use fig::watch::Watcher;
 use camera::*;
 
-extern crate directwrite;
-//use directwrite::font_collection::FontCollection;
-//use directwrite::enums::{FontStretch, FontStyle, FontWeight, InformationalStringId};
+// from the command line, kept for reloads
+#[derive(Default)]
+struct Options {
+    filament_overrides: stats::Overrides,
+    volume_override: Option<Bounds>,
+    extruder_offsets: bool,
+    // empty leaves every macro as it is
+    macros: Macros,
+    // a second file to diff against
+    compare: Option<PathBuf>,
+}
+
+// the flags Options::parse takes, for the usage lines
+const LOAD_USAGE: &str =
+    "[--extruder-offsets] [--macros printer.cfg] [--filament-diameter mm] [--filament-density g/cm3] [--filament-cost per-kg]";
+
+impl Options {
+    // the flags for how a file gets loaded, which every command takes.
+    // false if arg isnt one of them
+    fn parse(&mut self, arg: &str, args: &mut impl Iterator<Item = String>) -> bool {
+        match arg {
+            "--extruder-offsets" => self.extruder_offsets = true,
+            "--macros" => self.macros = macros_arg(args.next()),
+            "--filament-diameter" => self.filament_overrides.diameter = number_arg(arg, args.next()),
+            "--filament-density" => self.filament_overrides.density = number_arg(arg, args.next()),
+            "--filament-cost" => self.filament_overrides.cost = number_arg(arg, args.next()),
+            _ => return false,
+        }
+        true
+    }
 
+    // the file with its macros expanded and the extruder offsets applied,
+    // if those were asked for
+    fn open(&self, path: &Path) -> std::io::Result<(Toolpath, Source)> {
+        let (mut toolpath, source) = fig::loader::open(path, &self.macros)?;
+        if self.extruder_offsets {
+            offset_tools(&mut toolpath);
+        }
+        Ok((toolpath, source))
+    }
 
+    // whatever the file says about its filament, with the overrides on top
+    fn filaments(&self, toolpath: &Toolpath) -> Vec<stats::Filament> {
+        let mut filaments = stats::Filament::from_config(&toolpath.config);
+        self.filament_overrides.apply(&mut filaments);
+        filaments
+    }
+}
+
+// everything on screen besides the hud, each toggled with its own key
+struct Panels {
+    source: panel::SourcePanel,
+    inspector: pick::Inspector,
+    usage: usage::UsagePanel,
+    retraction: markers::RetractionPanel,
+    seams: markers::SeamPanel,
+    objects: outlines::ObjectPanel,
+    commands: commands::CommandPanel,
+    diff: compare::DiffPanel,
+    keys: keys::KeysPanel,
+}
+
+impl Panels {
+    fn new(text: &mut text::Layer, source: Source, objects: usize) -> Self {
+        Self {
+            source: panel::SourcePanel::new(text, source),
+            inspector: pick::Inspector::new(text),
+            usage: usage::UsagePanel::new(text),
+            retraction: markers::RetractionPanel::new(text),
+            seams: markers::SeamPanel::new(text),
+            objects: outlines::ObjectPanel::new(text, objects),
+            commands: commands::CommandPanel::new(text),
+            diff: compare::DiffPanel::new(text),
+            keys: keys::KeysPanel::new(text),
+        }
+    }
+
+    // the bottom corners fill up from the bottom, in the order here
+    fn stack(&self, text: &mut text::Layer) {
+        hud::stack(text, &[
+            self.usage.panel.label(),
+            self.retraction.panel.label(),
+            self.seams.panel.label(),
+            self.objects.panel.label(),
+        ]);
+        hud::stack(text, &[
+            self.inspector.label(),
+            self.commands.panel.label(),
+            self.diff.panel.label(),
+            self.keys.panel.label(),
+        ]);
+    }
+}
 
 struct Fig {
+    atlas: text::Atlas,
+    atlas_texture: wgpu::Texture,
+    watcher: Watcher,
+    // set from input()/update(), picked up in render() where we have a
+    // device to rebuild the buffers with
+    pending_path: Option<PathBuf>,
+    pending_reload: bool,
+    modifiers: ModifiersState,
+    // physical pixels from the top left, like winit has it
+    mouse: [f32; 2],
+
+    layers: Vec<Layer>,
+    // the topmost layer drawn, or everything if None
+    layer: Option<usize>,
+    toolpath: Toolpath,
+    // clicked on in the view
+    selected: Option<usize>,
+    playback: playback::Playback,
+    // the partly done move and a cross at the nozzle, see write_marker
+    marker_buffer: wgpu::Buffer,
+    estimate: Estimate,
+    // what the slicer thinks, in seconds
+    slicer_estimate: Option<f32>,
+    stats: Stats,
+    options: Options,
+    // the build volume, from the command line or the files config
+    volume: Option<Bounds>,
+    // runs of moves that leave it, drawn in red
+    outside: Vec<std::ops::Range<usize>>,
+    volume_buffer: wgpu::Buffer,
+    retraction: Analysis,
+    markers: markers::Markers,
+    seams: Seams,
+    seam_markers: markers::Markers,
+    // the moves split up by extruder, each drawn in its own color
+    tool_runs: Vec<(std::ops::Range<usize>, usize)>,
+    tool_markers: markers::Markers,
+    objects: Objects,
+    outlines: outlines::Outlines,
+    commands: Vec<Command>,
+    // tool_runs without the objects that are hidden, whats actually drawn
+    path_runs: Vec<(std::ops::Range<usize>, usize)>,
+    // the --compare file
+    other: Option<compare::Other>,
+
     glyph_bind_group: wgpu::BindGroup,
 
-    render_pipeline: wgpu::RenderPipeline,
+    pipelines: PathPipelines,
     vertex_buffer: wgpu::Buffer,
     vertex_count: u32,
     camera: Camera,
@@ -29,16 +185,267 @@ struct Fig {
 
     text_vertex_buffer: wgpu::Buffer,
     text_pipeline: wgpu::RenderPipeline,
-    text_buffer: text::Buffer,
+    text_layer: text::Layer,
+    hud: hud::Hud,
+    panels: Panels,
+    text_label_buffer: wgpu::Buffer,
+    text_glyph_buffer: wgpu::Buffer,
+    text_bind_group_layout: wgpu::BindGroupLayout,
     text_bind_group: wgpu::BindGroup,
 
+    screen_size: [u32; 2],
     screen_metadata_buffer: wgpu::Buffer,
 }
 
+// starting size of the text storage buffers, in bytes
+const TEXT_BUFFER_SIZE: u64 = 16 * 1024;
+
+fn text_storage_buffer(device: &wgpu::Device, label: &str, size: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_text_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    screen: &wgpu::Buffer,
+    labels: &wgpu::Buffer,
+    glyphs: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(
+        &wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: screen.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: labels.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: glyphs.as_entire_binding(),
+                },
+            ],
+            label: Some("text_bind_group"),
+        }
+    )
+}
+
+// see Fig::write_marker
+const MARKER_VERTICES: usize = 8;
+
+fn create_path_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    fragment_entry: &str,
+    topology: wgpu::PrimitiveTopology,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[
+                Vertex::desc()
+            ],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: fragment_entry,
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            // requires Features::POLYGON_MODE_LINE which iirc is not
+            // available on wasm. i not sorry i hate the web anyway
+            polygon_mode: wgpu::PolygonMode::Line,
+            // needs Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // needs Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+// runs the planner and prints how it compares to the slicer
+fn estimate_time(toolpath: &Toolpath) -> (Estimate, planner::Comparison) {
+    let estimate = planner::estimate(toolpath, planner::Limits::for_toolpath(toolpath));
+    let comparison = planner::compare(toolpath, &estimate);
+    println!("estimated print time: {}", hud::format_duration(comparison.planner));
+    if let Some(slicer) = comparison.slicer {
+        println!("  slicer: {}", hud::format_duration(slicer));
+    }
+    if let Some(progress) = comparison.progress {
+        println!("  M73: {}", hud::format_duration(progress));
+    }
+    if let Some(drift) = comparison.drift {
+        println!("  worst drift from M73: {}{}", if drift < 0.0 { "-" } else { "+" }, hud::format_duration(drift.abs()));
+    }
+    (estimate, comparison)
+}
+
+// filament per layer, feature and extruder, going by the config block in
+// the file unless the command line says otherwise
+fn filament_stats(toolpath: &Toolpath, layers: &[Layer], options: &Options) -> Stats {
+    let stats = stats::filament(toolpath, layers, &options.filaments(toolpath));
+    println!(
+        "filament used: {:.2}mm  {:.2}cm3  {:.2}g  cost {:.2}",
+        stats.total.length,
+        stats.total.volume / 1000.0,
+        stats.total.weight,
+        stats.total.cost,
+    );
+    stats
+}
+
+fn upload_toolpath(device: &wgpu::Device, toolpath: &Toolpath) -> (wgpu::Buffer, u32) {
+    let vertices: Vec<Vertex> = toolpath.points()
+        .map(|[x, y, z]| Vertex::at(x, y, z))
+        .collect();
+
+    let vertex_buffer = device.create_buffer_init(
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(vertices.as_slice()),
+            usage: wgpu::BufferUsages::VERTEX,
+        }
+    );
+
+    (vertex_buffer, vertices.len() as u32)
+}
+
+// the moves outside the build volume, grouped into runs that can be drawn
+// in one go each
+fn check_volume(toolpath: &Toolpath, volume: Option<Bounds>) -> Vec<std::ops::Range<usize>> {
+    let Some(volume) = volume else {
+        return Vec::new();
+    };
+    let check = volume::check(toolpath, &volume);
+    let mut runs: Vec<std::ops::Range<usize>> = Vec::new();
+    for i in check.outside.iter().copied() {
+        match runs.last_mut() {
+            Some(run) if run.end == i => run.end = i + 1,
+            _ => runs.push(i..i + 1),
+        }
+    }
+    if !check.outside.is_empty() {
+        println!(
+            "{} moves leave the build volume, the first at line {}",
+            check.outside.len(),
+            toolpath.moves[check.outside[0]].line,
+        );
+    }
+    runs
+}
+
+// the edges of the build volume as one line strip, going back over a few
+// of them to get round all twelve
+fn upload_volume(device: &wgpu::Device, volume: Option<Bounds>) -> wgpu::Buffer {
+    let vertices: Vec<Vertex> = match volume {
+        Some(Bounds { min, max }) => {
+            let corner = |i: usize| {
+                let x = if i & 1 == 0 { min[0] } else { max[0] };
+                let y = if i & 2 == 0 { min[1] } else { max[1] };
+                let z = if i & 4 == 0 { min[2] } else { max[2] };
+                Vertex::at(x, y, z)
+            };
+            // 0 1 3 2 round the bottom, 4 5 7 6 round the top
+            [0, 1, 3, 2, 0, 4, 5, 1, 5, 7, 3, 7, 6, 2, 6, 4]
+                .into_iter()
+                .map(corner)
+                .collect()
+        },
+        None => vec![Vertex::at(0.0, 0.0, 0.0); VOLUME_VERTICES as usize],
+    };
+    device.create_buffer_init(
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Volume Buffer"),
+            contents: bytemuck::cast_slice(vertices.as_slice()),
+            usage: wgpu::BufferUsages::VERTEX,
+        }
+    )
+}
+
+const VOLUME_VERTICES: u32 = 16;
+
+// everything drawn with the path shader, which is everything but the text
+struct PathPipelines {
+    path: wgpu::RenderPipeline,
+    highlight: wgpu::RenderPipeline,
+    outside: wgpu::RenderPipeline,
+    volume: wgpu::RenderPipeline,
+    // one per kind of retraction marker
+    retractions: Vec<wgpu::RenderPipeline>,
+    // external seams first so showing only those is the first one
+    seams: Vec<wgpu::RenderPipeline>,
+    // one per extruder color, as strips for the path and as lists for the
+    // tool change markers
+    tools: Vec<wgpu::RenderPipeline>,
+    tool_markers: Vec<wgpu::RenderPipeline>,
+    outlines: Vec<wgpu::RenderPipeline>,
+    compare: wgpu::RenderPipeline,
+}
+
+impl PathPipelines {
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let strip = wgpu::PrimitiveTopology::LineStrip;
+        let list = wgpu::PrimitiveTopology::LineList;
+        let pipeline = |entry: &str, topology| create_path_pipeline(device, layout, shader, format, entry, topology);
+        let pipelines = |entries: &[&str], topology| entries.iter().map(|entry| pipeline(entry, topology)).collect();
+        Self {
+            path: pipeline("fs_main", strip),
+            // the same again in a brighter color, for whatever the source
+            // panel has selected
+            highlight: pipeline("fs_highlight", strip),
+            outside: pipeline("fs_outside", strip),
+            volume: pipeline("fs_volume", strip),
+            retractions: pipelines(&markers::RETRACTION_ENTRIES, list),
+            seams: pipelines(&markers::SEAM_ENTRIES, list),
+            tools: pipelines(&markers::TOOL_ENTRIES, strip),
+            tool_markers: pipelines(&markers::TOOL_ENTRIES, list),
+            outlines: pipelines(&outlines::FRAGMENT_ENTRIES, list),
+            compare: pipeline(compare::FRAGMENT_ENTRY, strip),
+        }
+    }
+}
+
 impl Fig {
     fn init(
         state: &graphics::AppSkeleton,
-        vertices: Vec::<Vertex>,
+        path: PathBuf,
+        toolpath: Toolpath,
+        source: Source,
+        options: Options,
+        font: Box<dyn font::Backend>,
     ) -> Self {
 
         let screen_uniform_buffer = state.device.create_buffer_init(
@@ -50,68 +457,27 @@ impl Fig {
         );
 
         // ===== GLYPHS ======
-        /*
-        let factory = directwrite::Factory::new().unwrap();
-
-        let collection = FontCollection::system_font_collection(&factory, false).unwrap();
-        let lucidia_id = collection.find_family_by_name("Lucida Sans Typewriter").unwrap();
-        let lucidia_family = collection.family(lucidia_id).unwrap();
-        let lucidia_font = lucidia_family
-            .first_matching_font(FontWeight::NORMAL, FontStretch::Normal, FontStyle::Normal)
-            .unwrap();
-        println!(
-            "Font name: {:#?}",
-            lucidia_font.informational_strings(InformationalStringId::FullName)
-        );
-        println!("Face name: {:#?}", lucidia_font.face_name());
-        // this is from the directwrite crate, which is super cool, but also unfortunately
-        // does not have glyph rasterization. i *could* add it, except cargo is refusing
-        // to find any project metadata in the pulled git repo. fucking wonderful.
-        */
-
-        // i think im just going to use a font atlas or something for now
-        // resources/glyphatlas.bin is a 5x12 font atlas in 8 bit single channel format
-
-        let atlas_bytes: Vec<u8> = std::fs::read("src/resources/glyphatlas.bin")
-            .unwrap().iter()
-            .map(|v| match v { 0 => 0, _ => 255 })
-            .collect();
-
-        let atlas_size = wgpu::Extent3d {
-            width: 160,
-            height: 144,
-            depth_or_array_layers: 1,
-        };
+        // glyphs get rasterized into the atlas as theyre needed and the
+        // texture is reuploaded in update() whenever that happens
+        let mut atlas = text::Atlas::new(font, 1024, 1024);
 
         let atlas_texture = state.device.create_texture(
             &wgpu::TextureDescriptor {
-                size: atlas_size,
+                size: wgpu::Extent3d {
+                    width: atlas.width,
+                    height: atlas.height,
+                    depth_or_array_layers: 1,
+                },
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
+                format: wgpu::TextureFormat::R8Unorm,
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
                 label: Some("glyph_atlas"),
                 view_formats: &[]
             }
         );
 
-        state.queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &atlas_texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &atlas_bytes,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(160 * 4),
-                rows_per_image: Some(144),
-            },
-            atlas_size,
-        );
-
         let atlas_texture_view = atlas_texture.create_view(&wgpu::TextureViewDescriptor::default());
         let atlas_sampler = state.device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
@@ -128,7 +494,8 @@ impl Fig {
                 entries: &[
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        // the vertex shader needs the atlas size for texcoords
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
                             view_dimension: wgpu::TextureViewDimension::D2,
@@ -168,7 +535,8 @@ impl Fig {
         );        
 
         // ===== CAMERA ======
-        let camera = Camera {
+        let mut camera = Camera {
+            mode: Mode::Orbit,
             // position the camera one unit up and 2 units back
             // +z is out of the screen
             eye: (0.0, 500.0, 500.0).into(),
@@ -181,6 +549,9 @@ impl Fig {
             znear: 0.1,
             zfar: 1000.0,
         };
+        if let Some(bounds) = toolpath.bounds() {
+            camera.frame(bounds.min, bounds.max);
+        }
 
         let mut camera_uniform = CameraUniform::new();
         camera_uniform.update_view_proj(&camera);
@@ -240,78 +611,47 @@ impl Fig {
                 push_constant_ranges: &[],
             });
 
-        let vertex_buffer = state.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(vertices.as_slice()),
-                usage: wgpu::BufferUsages::VERTEX,
-            }
-        );
-
-        let render_pipeline = state.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[
-                    Vertex::desc()
-                ],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: state.config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::LineStrip,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                // requires Features::POLYGON_MODE_LINE which iirc is not
-                // available on wasm. i not sorry i hate the web anyway
-                polygon_mode: wgpu::PolygonMode::Line,
-                // needs Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // needs Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
+        let (vertex_buffer, vertex_count) = upload_toolpath(&state.device, &toolpath);
+        let (estimate, comparison) = estimate_time(&toolpath);
+        let layers = toolpath.layers();
+        let stats = filament_stats(&toolpath, &layers, &options);
+        let volume = options.volume_override.or_else(|| volume::from_config(&toolpath.config));
+        let outside = check_volume(&toolpath, volume);
+        let volume_buffer = upload_volume(&state.device, volume);
+        let retraction = retraction::analyze(&toolpath, &layers);
+        let markers = markers::Markers::retraction(&state.device, &retraction);
+        let seams = seams::find(&toolpath, &layers);
+        let seam_markers = markers::Markers::seams(&state.device, &seams);
+        let tool_runs = tools::runs(&toolpath);
+        let tool_markers = markers::Markers::tool_changes(&state.device, &tools::changes(&toolpath));
+        let other = options.compare.clone().and_then(|other| {
+            let base = Side::new(&toolpath, &layers, stats.total, estimate.total, comparison.slicer);
+            load_other(&state.device, other, &options, base)
+        });
+        let marker_buffer = state.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Marker Buffer"),
+            size: (MARKER_VERTICES * std::mem::size_of::<Vertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
+
+        let pipelines = PathPipelines::new(&state.device, &render_pipeline_layout, &shader, state.config.format);
         // ====== END WIREFRAME PIPELINE ======
 
         // ====== TEXT BIND GROUP ======
-        let text_storage = text::Buffer::from("Hello World");
-        let mut text_uniform = text::Metadata::from(&text_storage);
-        text_uniform.pixel_size = [30; 2];
-        text_uniform.pixel_position = [20; 2];
-        text_uniform.kerning = 100;
-
-        let text_uniform_buffer = state.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Text Metadata Buffer"),
-                contents: bytemuck::cast_slice(&[text_uniform]),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            }
-        );
+        let mut text_layer = text::Layer::new();
+        text_layer.set_scale(state.window().scale_factor() as f32);
+        let hud = hud::Hud::new(&mut text_layer);
+        let objects = objects::find(&toolpath, &source);
+        let outlines = outlines::Outlines::new(&state.device, &objects);
+        let commands = macros::commands(&toolpath, &source);
+        let path_runs = tool_runs.clone();
+        let panels = Panels::new(&mut text_layer, source, objects.len());
 
-        let text_storage_buffer = state.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Text Storage Buffer"),
-                contents: text_storage.pack_glyphs(),
-                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            }
-        );
+        // both of these grow (and the bind group gets rebuilt) in
+        // prepare_text() if the labels outgrow them
+        let text_label_buffer = text_storage_buffer(&state.device, "Text Label Buffer", TEXT_BUFFER_SIZE);
+        let text_glyph_buffer = text_storage_buffer(&state.device, "Text Glyph Buffer", TEXT_BUFFER_SIZE);
 
         // for the glyph textures i think i either want to use onion textures
         // or the descriptor thing mentioned here:
@@ -344,11 +684,11 @@ impl Fig {
                         },
                         count: None,
                     },
-                    wgpu::BindGroupLayoutEntry { // text metadata
+                    wgpu::BindGroupLayoutEntry { // label metadata
                         binding: 1,
                         visibility: wgpu::ShaderStages::VERTEX,
                         ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
                             has_dynamic_offset: false,
                             min_binding_size: None,
                         },
@@ -369,25 +709,12 @@ impl Fig {
             }
         );
 
-        let text_bind_group = state.device.create_bind_group(
-            &wgpu::BindGroupDescriptor {
-                layout: &text_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: screen_uniform_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: text_uniform_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: text_storage_buffer.as_entire_binding(),
-                    },
-                ],
-                label: Some("text_bind_group"),
-            }
+        let text_bind_group = create_text_bind_group(
+            &state.device,
+            &text_bind_group_layout,
+            &screen_uniform_buffer,
+            &text_label_buffer,
+            &text_glyph_buffer,
         );
         // ====== END TEXT BIND GROUP ======
 
@@ -465,11 +792,45 @@ impl Fig {
         });
 
         Self {
+            atlas,
+            atlas_texture,
+
+            watcher: Watcher::new(path),
+            pending_path: None,
+            pending_reload: false,
+            modifiers: ModifiersState::empty(),
+            mouse: [0.0; 2],
+
+            layers,
+            layer: None,
+            playback: playback::Playback::new(estimate.moves.iter().copied()),
+            slicer_estimate: comparison.slicer,
+            estimate,
+            stats,
+            options,
+            volume,
+            outside,
+            volume_buffer,
+            retraction,
+            markers,
+            seams,
+            seam_markers,
+            tool_runs,
+            tool_markers,
+            objects,
+            outlines,
+            commands,
+            path_runs,
+            other,
+            marker_buffer,
+            toolpath,
+            selected: None,
+
             glyph_bind_group,
 
-            render_pipeline,
+            pipelines,
             vertex_buffer,
-            vertex_count: vertices.len() as u32,
+            vertex_count,
             camera,
             camera_uniform,
             camera_buffer,
@@ -478,12 +839,323 @@ impl Fig {
 
             text_vertex_buffer,
             text_pipeline,
-            text_buffer: text_storage,
+            text_layer,
+            hud,
+            panels,
+            text_label_buffer,
+            text_glyph_buffer,
+            text_bind_group_layout,
             text_bind_group,
 
+            screen_size: [state.config.width, state.config.height],
             screen_metadata_buffer: screen_uniform_buffer,
         }
     }
+
+    // swaps the toolpath out for a different file. if it cant be read we
+    // just keep showing whatever we already had. reloads of the same file
+    // keep the camera and layer where they were
+    fn load(&mut self, device: &wgpu::Device, path: PathBuf, reload: bool) {
+        let (toolpath, source) = match self.options.open(&path) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                eprintln!("error opening {}: {}", path.display(), e);
+                return;
+            },
+        };
+        println!("loaded {} ({} moves)", path.display(), toolpath.len());
+        if reload {
+            self.watcher.reloaded();
+        }
+
+        self.objects = objects::find(&toolpath, &source);
+        self.outlines = outlines::Outlines::new(device, &self.objects);
+        self.panels.objects.reset(&mut self.text_layer, self.objects.len());
+        self.commands = macros::commands(&toolpath, &source);
+        self.panels.commands.panel.invalidate();
+        self.panels.source.set_source(source, reload);
+
+        (self.vertex_buffer, self.vertex_count) = upload_toolpath(device, &toolpath);
+        self.layers = toolpath.layers();
+        let (estimate, comparison) = estimate_time(&toolpath);
+        self.playback.set_durations(estimate.moves.iter().copied());
+        self.estimate = estimate;
+        self.slicer_estimate = comparison.slicer;
+        self.stats = filament_stats(&toolpath, &self.layers, &self.options);
+        self.panels.usage.panel.invalidate();
+        self.volume = self.options.volume_override.or_else(|| volume::from_config(&toolpath.config));
+        self.outside = check_volume(&toolpath, self.volume);
+        self.volume_buffer = upload_volume(device, self.volume);
+        self.retraction = retraction::analyze(&toolpath, &self.layers);
+        self.markers = markers::Markers::retraction(device, &self.retraction);
+        self.panels.retraction.panel.invalidate();
+        self.seams = seams::find(&toolpath, &self.layers);
+        self.seam_markers = markers::Markers::seams(device, &self.seams);
+        self.panels.seams.panel.invalidate();
+        self.tool_runs = tools::runs(&toolpath);
+        self.path_runs = self.tool_runs.clone();
+        self.tool_markers = markers::Markers::tool_changes(device, &tools::changes(&toolpath));
+        if let Some(other) = self.other.as_mut() {
+            other.set_base(Side::new(&toolpath, &self.layers, self.stats.total, self.estimate.total, self.slicer_estimate));
+            self.panels.diff.panel.invalidate();
+        }
+        let bounds = toolpath.bounds();
+        self.toolpath = toolpath;
+        // move indices dont mean anything in a different toolpath
+        self.select(None);
+        if reload {
+            self.layer = self.layer.map(|l| l.min(self.layers.len().saturating_sub(1)));
+            return;
+        }
+
+        if let Some(bounds) = bounds {
+            self.camera.frame(bounds.min, bounds.max);
+        }
+        self.layer = None;
+        self.watcher = Watcher::new(path);
+    }
+
+    fn select(&mut self, selected: Option<usize>) {
+        self.selected = selected;
+        let selected = selected.and_then(|i| self.toolpath.moves.get(i).map(|m| (i, m)));
+        if let Some((_, m)) = selected {
+            self.panels.source.set_line(m.line);
+        }
+        self.panels.inspector.show(&mut self.text_layer, selected, &self.layers, self.panels.source.source());
+    }
+
+    // whether the --compare file gets the right half of the window
+    fn split(&self) -> bool {
+        self.other.is_some() && self.panels.diff.view() == compare::View::Split
+    }
+
+    // the part of the window this file is drawn in, from the top left
+    fn viewport(&self) -> [u32; 2] {
+        let [width, height] = self.screen_size;
+        if self.split() {
+            [(width / 2).max(1), height]
+        } else {
+            [width, height]
+        }
+    }
+
+    // picks whatever segment is under the mouse, out of the ones being drawn
+    fn select_at_mouse(&mut self) {
+        let [width, height] = self.viewport();
+        let screen = [width as f32, height as f32];
+        let cursor = [self.mouse[0], screen[1] - self.mouse[1]];
+        let visible = (self.visible_vertices() as usize).saturating_sub(1);
+        // up to the layer being looked at, leaving out hidden objects
+        let drawn: Vec<std::ops::Range<usize>> = self.path_runs
+            .iter()
+            .map(|(run, _)| run.start..run.end.min(visible))
+            .filter(|run| !run.is_empty())
+            .collect();
+        // side by side the right half is the other file, which cant be
+        // picked from
+        let picked = if cursor[0] < screen[0] {
+            pick::pick(
+                &self.toolpath.moves,
+                &drawn,
+                self.camera.build_view_projection_matrix(),
+                screen,
+                cursor,
+            )
+        } else {
+            None
+        };
+        self.select(picked);
+    }
+
+    // the move the source panel is on, if its open
+    fn cursor(&self) -> Option<usize> {
+        if !self.panels.source.visible() {
+            return None;
+        }
+        self.toolpath.move_at_line(self.panels.source.line())
+    }
+
+    // puts the source panel on the next or previous move
+    fn step_move(&mut self, forward: bool) {
+        let next = match (self.cursor(), forward) {
+            (None, _) => 0,
+            (Some(i), true) => (i + 1).min(self.toolpath.len().saturating_sub(1)),
+            (Some(i), false) => i.saturating_sub(1),
+        };
+        if let Some(m) = self.toolpath.moves.get(next) {
+            self.panels.source.set_line(m.line);
+        }
+    }
+
+    fn step_layer(&mut self, up: bool) {
+        let top = self.layers.len().saturating_sub(1);
+        self.layer = match (self.layer, up) {
+            (None, true) => None,
+            (None, false) => Some(top),
+            (Some(l), true) if l >= top => None,
+            (Some(l), true) => Some(l + 1),
+            (Some(l), false) => Some(l.saturating_sub(1)),
+        };
+    }
+
+    // how much of the strip to draw for the selected layer
+    fn visible_vertices(&self) -> u32 {
+        let visible = match self.layer.and_then(|l| self.layers.get(l)) {
+            // the strip has one more point than there are moves
+            Some(layer) => (layer.moves.end as u32 + 1).min(self.vertex_count),
+            None => self.vertex_count,
+        };
+        // up to the start of the move the nozzle is on, write_marker draws
+        // the rest of the way
+        if self.playback.active() {
+            visible.min(self.playback.current() as u32 + 1)
+        } else {
+            visible
+        }
+    }
+
+    // fills in the marker buffer for playback. returns false if theres
+    // nothing to draw
+    fn write_marker(&self, queue: &wgpu::Queue) -> bool {
+        if !self.playback.active() {
+            return false;
+        }
+        let current = self.playback.current();
+        let (Some(m), Some(nozzle)) = (
+            self.toolpath.moves.get(current),
+            self.playback.nozzle(&self.toolpath.moves),
+        ) else {
+            return false;
+        };
+        // keep the cross about the same size on screen however far out
+        // the camera is
+        let size = {
+            use cgmath::InnerSpace;
+            (self.camera.target - self.camera.eye).magnitude() * 0.01
+        };
+        let [x, y, z] = nozzle;
+        let vertices = [
+            Vertex::at(m.start[0], m.start[1], m.start[2]),
+            Vertex::at(x, y, z),
+            Vertex::at(x - size, y, z),
+            Vertex::at(x + size, y, z),
+            Vertex::at(x, y - size, z),
+            Vertex::at(x, y + size, z),
+            Vertex::at(x, y, z - size),
+            Vertex::at(x, y, z + size),
+        ];
+        queue.write_buffer(&self.marker_buffer, 0, bytemuck::cast_slice(&vertices));
+        true
+    }
+
+    // lays the labels out for this frame and gets them (and any new glyphs)
+    // onto the gpu, growing the storage buffers if they dont fit anymore
+    fn prepare_text(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let view_proj = self.camera.build_view_projection_matrix();
+        let viewport = self.viewport();
+        self.text_layer.layout(&mut self.atlas, self.screen_size, view_proj, viewport);
+
+        let labels = self.text_layer.pack_labels();
+        let glyphs = self.text_layer.pack_glyphs();
+        let mut grown = false;
+        if labels.len() as u64 > self.text_label_buffer.size() {
+            let size = (labels.len() as u64).next_power_of_two();
+            self.text_label_buffer = text_storage_buffer(device, "Text Label Buffer", size);
+            grown = true;
+        }
+        if glyphs.len() as u64 > self.text_glyph_buffer.size() {
+            let size = (glyphs.len() as u64).next_power_of_two();
+            self.text_glyph_buffer = text_storage_buffer(device, "Text Glyph Buffer", size);
+            grown = true;
+        }
+        if grown {
+            self.text_bind_group = create_text_bind_group(
+                device,
+                &self.text_bind_group_layout,
+                &self.screen_metadata_buffer,
+                &self.text_label_buffer,
+                &self.text_glyph_buffer,
+            );
+        }
+        queue.write_buffer(&self.text_label_buffer, 0, labels);
+        queue.write_buffer(&self.text_glyph_buffer, 0, glyphs);
+
+        if self.atlas.take_dirty() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.atlas_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                self.atlas.pixels(),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.atlas.width),
+                    rows_per_image: Some(self.atlas.height),
+                },
+                self.atlas_texture.size(),
+            );
+        }
+    }
+
+    fn open_dialog(&mut self) {
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter("G-code", &["gcode", "gco", "g"]);
+        if let Some(directory) = self.watcher.path().parent() {
+            dialog = dialog.set_directory(directory);
+        }
+        if let Some(path) = dialog.pick_file() {
+            self.pending_path = Some(path);
+        }
+    }
+
+    // does whatever the key table says, false when it doesnt apply right now
+    // so the key can fall through to the camera
+    fn act(&mut self, action: keys::Action) -> bool {
+        use keys::Action;
+        let text = &mut self.text_layer;
+        match action {
+            Action::Open => self.open_dialog(),
+            Action::Hud => self.hud.toggle(text),
+            Action::Keys => self.panels.keys.panel.toggle(text),
+            Action::CameraMode => self.camera.toggle_mode(),
+            Action::LayerUp => self.step_layer(true),
+            Action::LayerDown => self.step_layer(false),
+            Action::Source => self.panels.source.toggle(text),
+            Action::PreviousMove | Action::NextMove if self.panels.source.visible() => {
+                self.step_move(action == Action::NextMove);
+            },
+            Action::PreviousMove | Action::NextMove => return false,
+            Action::Playback => self.playback.toggle(),
+            Action::PlayPause => self.playback.play_pause(),
+            Action::StepBack => self.playback.step(false),
+            Action::StepForward => self.playback.step(true),
+            Action::Slower => self.playback.slower(),
+            Action::Faster => self.playback.faster(),
+            Action::Usage => self.panels.usage.panel.toggle(text),
+            Action::Retractions => self.panels.retraction.panel.toggle(text),
+            Action::Objects => self.panels.objects.toggle(text),
+            Action::NextObject => self.panels.objects.select_next(),
+            Action::HideObject | Action::IsolateObject => {
+                let changed = if action == Action::HideObject {
+                    self.panels.objects.toggle_hidden()
+                } else {
+                    self.panels.objects.toggle_isolated()
+                };
+                if changed {
+                    let shown = self.objects.shown(self.panels.objects.hidden(), self.panels.objects.isolated(), self.toolpath.len());
+                    self.path_runs = intersect_runs(&self.tool_runs, &shown);
+                }
+            },
+            Action::Commands => self.panels.commands.panel.toggle(text),
+            Action::Seams => self.panels.seams.cycle(text),
+            Action::DiffView if self.other.is_some() => self.panels.diff.cycle(),
+            Action::DiffView => return false,
+            Action::Diff => self.panels.diff.panel.toggle(text),
+        }
+        true
+    }
 }
 
 impl graphics::Application for Fig {
@@ -491,11 +1163,80 @@ impl graphics::Application for Fig {
         wgpu::Features::POLYGON_MODE_LINE
     }
 
-    fn input(&mut self, event: &winit::event::WindowEvent) -> bool {
-        self.camera_controller.process_events(event)
+    fn input(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::DroppedFile(path) => {
+                self.pending_path = Some(path.clone());
+                true
+            },
+            // let this through so the skeleton reconfigures the surface too
+            WindowEvent::Resized(size) => {
+                if size.width > 0 && size.height > 0 {
+                    self.screen_size = [size.width, size.height];
+                }
+                false
+            },
+            // moved to a monitor with a different dpi. the skeleton resizes
+            // the surface, the text needs laying out again at the new scale
+            WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } => {
+                self.text_layer.set_scale(*scale_factor as f32);
+                if new_inner_size.width > 0 && new_inner_size.height > 0 {
+                    self.screen_size = [new_inner_size.width, new_inner_size.height];
+                }
+                false
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                self.mouse = [position.x as f32, position.y as f32];
+                false
+            },
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.select_at_mouse();
+                true
+            },
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                false
+            },
+            WindowEvent::MouseWheel { delta, .. } if self.panels.source.visible() => {
+                // wheel up goes back towards the start of the file
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => -y * 3.0,
+                    MouseScrollDelta::PixelDelta(position) => -position.y as f32 / 16.0,
+                };
+                self.panels.source.scroll(lines.round() as i32);
+                true
+            },
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key),
+                    ..
+                },
+                ..
+            } => match keys::action(*key, self.modifiers) {
+                Some(action) if self.act(action) => true,
+                _ => self.camera_controller.process_events(event),
+            },
+            _ => self.camera_controller.process_events(event),
+        }
     }
 
     fn update(&mut self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.screen_metadata_buffer, 0, bytemuck::cast_slice(&self.screen_size));
+
+        if self.watcher.poll() {
+            self.pending_reload = true;
+        }
+
+        self.playback.tick();
+        // side by side both halves share the camera, so it gets the shape of
+        // a half
+        let [width, height] = self.viewport();
+        self.camera.aspect = width as f32 / height as f32;
         self.camera_controller.update_camera(&mut self.camera);
         self.camera_uniform.update_view_proj(&self.camera);
         queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
@@ -507,6 +1248,36 @@ impl graphics::Application for Fig {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
     ) -> Result<(), wgpu::SurfaceError> {
+        if let Some(path) = self.pending_path.take() {
+            self.load(device, path, false);
+        } else if self.pending_reload {
+            self.pending_reload = false;
+            self.load(device, self.watcher.path().to_path_buf(), true);
+        }
+        let info = hud::Info {
+            path: self.watcher.path(),
+            layers: &self.layers,
+            layer: self.layer,
+            moves: self.toolpath.len(),
+            estimate: &self.estimate,
+            slicer_estimate: self.slicer_estimate,
+            camera: &self.camera,
+            playback: &self.playback,
+            outside: self.volume.map(|_| self.outside.iter().map(|run| run.len()).sum()),
+        };
+        self.hud.update(&mut self.text_layer, &info);
+        self.panels.source.update(&mut self.text_layer);
+        self.panels.usage.update(&mut self.text_layer, &self.stats, self.layer);
+        self.panels.retraction.update(&mut self.text_layer, &self.retraction, self.layer);
+        self.panels.seams.update(&mut self.text_layer, &self.seams, self.layer);
+        self.panels.objects.update(&mut self.text_layer, &self.objects);
+        self.panels.commands.update(&mut self.text_layer, &self.commands, &self.options.macros, &self.layers, self.layer);
+        self.panels.diff.update(&mut self.text_layer, self.other.as_ref(), self.layer);
+        self.panels.keys.update(&mut self.text_layer);
+        self.panels.stack(&mut self.text_layer);
+        self.prepare_text(device, queue);
+        let draw_marker = self.write_marker(queue);
+
         let output = surface.get_current_texture()?;
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
 
@@ -537,14 +1308,95 @@ impl graphics::Application for Fig {
                 ],
                 depth_stencil_attachment: None,
             });
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.draw(0..self.vertex_count, 0..1);    
+            let split = self.split();
+            if split {
+                let [width, height] = self.viewport();
+                render_pass.set_viewport(0.0, 0.0, width as f32, height as f32, 0.0, 1.0);
+            }
+            if self.volume.is_some() {
+                render_pass.set_pipeline(&self.pipelines.volume);
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, self.volume_buffer.slice(..));
+                render_pass.draw(0..VOLUME_VERTICES, 0..1);
+            }
+            if self.panels.objects.panel.visible() {
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                self.outlines.draw(&mut render_pass, &self.pipelines.outlines, self.panels.objects.selected());
+            }
+            if self.vertex_count > 0 {
+                render_pass.set_pipeline(&self.pipelines.path);
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                // move i runs from point i to point i + 1
+                let visible = self.visible_vertices();
+                for (run, tool) in self.path_runs.iter() {
+                    let end = (run.end as u32 + 1).min(visible);
+                    if (run.start as u32) + 1 < end {
+                        render_pass.set_pipeline(&self.pipelines.tools[tool % self.pipelines.tools.len()]);
+                        render_pass.draw(run.start as u32..end, 0..1);
+                    }
+                }
+
+                render_pass.set_pipeline(&self.pipelines.outside);
+                for run in self.outside.iter() {
+                    let end = (run.end as u32 + 1).min(visible);
+                    if (run.start as u32) + 1 < end {
+                        render_pass.draw(run.start as u32..end, 0..1);
+                    }
+                }
+
+                // move i runs from point i to point i + 1
+                render_pass.set_pipeline(&self.pipelines.highlight);
+                if let Some(object) = self.panels.objects.selected().and_then(|i| self.objects.objects.get(i)) {
+                    for (run, _) in intersect_runs(&self.path_runs, &object.moves) {
+                        let end = (run.end as u32 + 1).min(visible);
+                        if (run.start as u32) + 1 < end {
+                            render_pass.draw(run.start as u32..end, 0..1);
+                        }
+                    }
+                }
+                for i in self.cursor().into_iter().chain(self.selected) {
+                    render_pass.draw(i as u32..i as u32 + 2, 0..1);
+                }
+
+                if self.panels.retraction.panel.visible() {
+                    self.markers.draw(&mut render_pass, &self.pipelines.retractions, visible.saturating_sub(1) as usize);
+                }
+                let seam_pipelines = match self.panels.seams.mode() {
+                    markers::SeamMode::Off => &self.pipelines.seams[..0],
+                    markers::SeamMode::All => &self.pipelines.seams[..],
+                    markers::SeamMode::External => &self.pipelines.seams[..1],
+                };
+                self.seam_markers.draw(&mut render_pass, seam_pipelines, visible.saturating_sub(1) as usize);
+                self.tool_markers.draw(&mut render_pass, &self.pipelines.tool_markers, visible.saturating_sub(1) as usize);
+            }
+            if draw_marker {
+                render_pass.set_pipeline(&self.pipelines.path);
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, self.marker_buffer.slice(..));
+                render_pass.draw(0..2, 0..1);
+                render_pass.set_pipeline(&self.pipelines.highlight);
+                render_pass.draw(2..4, 0..1);
+                render_pass.draw(4..6, 0..1);
+                render_pass.draw(6..8, 0..1);
+            }
+            if let Some(other) = self.other.as_ref().filter(|_| self.panels.diff.view() != compare::View::Off) {
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                if split {
+                    let [width, height] = self.viewport();
+                    render_pass.set_viewport(width as f32, 0.0, width as f32, height as f32, 0.0, 1.0);
+                    if self.volume.is_some() {
+                        render_pass.set_pipeline(&self.pipelines.volume);
+                        render_pass.set_vertex_buffer(0, self.volume_buffer.slice(..));
+                        render_pass.draw(0..VOLUME_VERTICES, 0..1);
+                    }
+                }
+                other.draw(&mut render_pass, &self.pipelines.compare, self.layer);
+            }
         }
 
         // text pass
-        if self.text_buffer.len() > 0 {
+        if self.text_layer.glyph_count() > 0 {
             let mut text_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Text Pass"),
                 color_attachments: &[
@@ -565,7 +1417,7 @@ impl graphics::Application for Fig {
             text_pass.set_bind_group(0, &self.text_bind_group, &[]);
             text_pass.set_bind_group(1, &self.glyph_bind_group, &[]);
             text_pass.set_vertex_buffer(0, self.text_vertex_buffer.slice(..));
-            text_pass.draw(0..6, 0..self.text_buffer.len() as u32);
+            text_pass.draw(0..6, 0..self.text_layer.glyph_count() as u32);
         }
     
         // submit will accept anything that implements IntoIter
@@ -576,57 +1428,273 @@ impl graphics::Application for Fig {
     }
 }
 
+#[cfg(all(windows, feature = "directwrite"))]
+fn system_font(family: &str) -> Option<Box<dyn font::Backend>> {
+    match font::directwrite::Font::system(family) {
+        Ok(font) => Some(Box::new(font)),
+        Err(e) => {
+            eprintln!("error loading system font {}: {:?}", family, e);
+            None
+        },
+    }
+}
+
+#[cfg(not(all(windows, feature = "directwrite")))]
+fn system_font(family: &str) -> Option<Box<dyn font::Backend>> {
+    eprintln!("cant look up system font {}, fig was built without directwrite", family);
+    None
+}
+
+fn load_font(path: Option<String>, family: Option<String>) -> Box<dyn font::Backend> {
+    if let Some(font) = family.and_then(|family| system_font(&family)) {
+        return font;
+    }
+    match path {
+        Some(path) => Box::new(font::portable::Font::open(&path).expect("Error opening font!")),
+        None => Box::new(font::portable::Font::bundled()),
+    }
+}
+
+// the value after a flag, ignored with a warning if its not a number
+fn number_arg(flag: &str, value: Option<String>) -> Option<f32> {
+    let parsed = value.as_deref().and_then(|v| v.parse().ok());
+    if parsed.is_none() {
+        eprintln!("{} needs a number, got {:?}", flag, value.unwrap_or_default());
+    }
+    parsed
+}
+
+// fig info [--json] [--extruder-offsets] [--macros printer.cfg] [--filament-*]
+// file.gcode, a summary of the file on stdout without opening a window
+fn info(mut args: impl Iterator<Item = String>) {
+    let mut path = None;
+    let mut json = false;
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        if options.parse(&arg, &mut args) {
+            continue;
+        }
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("usage: fig info [--json] {} file.gcode", LOAD_USAGE);
+        std::process::exit(2);
+    };
+
+    let (toolpath, source) = match options.open(&path) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("error opening {}: {}", path.display(), e);
+            std::process::exit(1);
+        },
+    };
+    let summary = fig::info::Summary::new(&toolpath, &source, &options.filaments(&toolpath));
+    if json {
+        println!("{}", summary.to_json());
+    } else {
+        println!("file        {}", path.display());
+        println!("{}", summary);
+    }
+}
+
+// the parts of each run that are in one of the shown ranges. both are in
+// file order
+fn intersect_runs(runs: &[(std::ops::Range<usize>, usize)], shown: &[std::ops::Range<usize>]) -> Vec<(std::ops::Range<usize>, usize)> {
+    let mut parts = Vec::new();
+    for (run, tool) in runs.iter() {
+        let first = shown.partition_point(|range| range.end <= run.start);
+        for range in shown[first..].iter().take_while(|range| range.start < run.end) {
+            parts.push((run.start.max(range.start)..run.end.min(range.end), *tool));
+        }
+    }
+    parts
+}
+
+// moves whatever the other extruders print by their offsets from the
+// files config, for --extruder-offsets
+fn offset_tools(toolpath: &mut Toolpath) {
+    let offsets = tools::offsets_from_config(&toolpath.config);
+    if offsets.is_empty() {
+        eprintln!("no extruder_offset in the file, drawing every extruder where the gcode says");
+    }
+    tools::apply_offsets(toolpath, &offsets);
+}
+
+// the --macros argument, a klipper config with [gcode_macro] sections to
+// expand. without one the macros stay as they are
+fn macros_arg(value: Option<String>) -> Macros {
+    let Some(path) = value else {
+        eprintln!("--macros needs a klipper config file");
+        return Macros::default();
+    };
+    match Macros::open(&path) {
+        Ok(macros) => {
+            if macros.is_empty() {
+                eprintln!("no [gcode_macro] sections in {}", path);
+            }
+            macros
+        },
+        Err(e) => {
+            eprintln!("error reading {}: {}", path, e);
+            Macros::default()
+        },
+    }
+}
+
+// loads a file to diff the way the viewer would load it, and works out
+// everything that gets compared
+fn measure(path: &Path, options: &Options) -> std::io::Result<(Toolpath, Side)> {
+    let (toolpath, _) = options.open(path)?;
+    let side = Side::measure(&toolpath, &options.filaments(&toolpath));
+    Ok((toolpath, side))
+}
+
+// the --compare file, with the same options as the one its compared
+// against. base is that ones side of the diff
+fn load_other(device: &wgpu::Device, path: PathBuf, options: &Options, base: Side) -> Option<compare::Other> {
+    match measure(&path, options) {
+        Ok((toolpath, side)) => {
+            println!("comparing against {} ({} moves)", path.display(), toolpath.len());
+            Some(compare::Other::new(device, path, &toolpath, base, side))
+        },
+        Err(e) => {
+            eprintln!("error opening {}: {}", path.display(), e);
+            None
+        },
+    }
+}
+
+// the --volume argument, WxDxH or x,y,z:x,y,z. checking against the
+// wrong volume would be worse than not checking, so anything else is a
+// usage error
+fn volume_arg(value: Option<String>) -> Bounds {
+    match value.as_deref().and_then(volume::parse) {
+        Some(volume) => volume,
+        None => {
+            eprintln!("--volume needs WxDxH or minx,miny,minz:maxx,maxy,maxz, got {:?}", value.unwrap_or_default());
+            std::process::exit(2);
+        },
+    }
+}
+
+// fig lint [--volume WxDxH] [--extruder-offsets] [--macros printer.cfg] [--filament-*]
+// file.gcode, prints any problems with the file and exits with 1 if theres
+// anything that would ruin the print
+fn lint(mut args: impl Iterator<Item = String>) {
+    let mut path = None;
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        if options.parse(&arg, &mut args) {
+            continue;
+        }
+        match arg.as_str() {
+            "--volume" => options.volume_override = Some(volume_arg(args.next())),
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("usage: fig lint [--volume WxDxH] {} file.gcode", LOAD_USAGE);
+        std::process::exit(2);
+    };
+
+    let (toolpath, source) = match options.open(&path) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("error opening {}: {}", path.display(), e);
+            std::process::exit(1);
+        },
+    };
+    let volume = options.volume_override.or_else(|| volume::from_config(&toolpath.config));
+    if volume.is_none() {
+        eprintln!("{}: no build volume in the file, pass --volume to check against one", path.display());
+    }
+    let issues = fig::lint::lint(&toolpath, &source, volume.as_ref());
+    for issue in issues.iter() {
+        println!("{}:{}", path.display(), issue);
+    }
+    if issues.iter().any(|issue| issue.severity == fig::lint::Severity::Error) {
+        std::process::exit(1);
+    }
+}
+
+// fig diff [--extruder-offsets] [--macros printer.cfg] [--filament-*] a.gcode b.gcode,
+// how b differs from a in time, filament and what each layer prints
+fn diff(mut args: impl Iterator<Item = String>) {
+    let mut paths = Vec::new();
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        if !options.parse(&arg, &mut args) {
+            paths.push(PathBuf::from(arg));
+        }
+    }
+    let [a, b] = paths.as_slice() else {
+        eprintln!("usage: fig diff {} a.gcode b.gcode", LOAD_USAGE);
+        std::process::exit(2);
+    };
+
+    let [a_side, b_side] = [a, b].map(|path| match measure(path, &options) {
+        Ok((_, side)) => side,
+        Err(e) => {
+            eprintln!("error opening {}: {}", path.display(), e);
+            std::process::exit(1);
+        },
+    });
+    println!("a           {}", a.display());
+    println!("b           {}", b.display());
+    println!("{}", Diff::new(a_side, b_side));
+}
+
 fn main() {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if(|arg| arg == "info").is_some() {
+        info(args);
+        return;
+    }
+    if args.next_if(|arg| arg == "lint").is_some() {
+        lint(args);
+        return;
+    }
+    if args.next_if(|arg| arg == "diff").is_some() {
+        diff(args);
+        return;
+    }
+
+    // fig [--font font.ttf] [--font-family name] [--filament-diameter mm]
+    //     [--filament-density g/cm3] [--filament-cost per-kg] [--volume WxDxH]
+    //     [--extruder-offsets] [--macros printer.cfg] [--compare other.gcode]
+    //     [file.gcode]
+    let mut path = PathBuf::from("test1.gcode");
+    let mut font_path = None;
+    let mut font_family = None;
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        if options.parse(&arg, &mut args) {
+            continue;
+        }
+        match arg.as_str() {
+            "--volume" => options.volume_override = Some(volume_arg(args.next())),
+            "--compare" => options.compare = args.next().map(PathBuf::from),
+            "--font" => font_path = args.next(),
+            "--font-family" => font_family = args.next(),
+            _ => path = PathBuf::from(arg),
+        }
+    }
+
     let skeleton = pollster::block_on(graphics::build::<Fig>("fig"));
     println!("built window with size: {:?}", skeleton.screen_size);
+    let font = load_font(font_path, font_family);
+
     // open gcode file
-    let mut reader = reader::BufferedReader::open("test1.gcode")
-        .expect("Error opening file!");
-    let mut buffer = String::new();
-
-    // create vertex buffer
-    let mut vertices = Vec::<Vertex>::new();
-    let mut is_first = true;
-
-    while let Some(line) = reader.read_line(&mut buffer) {
-        //println!("{}", line.trim());
-        // if we successfully lex a command parse it and match it to ones
-        // we care about
-        if let Ok(Some(fields)) = gcode::lexer::lex(line.as_str()) {
-            match gcode::parser::parse(fields) {
-                Ok(GCommand::G1 {
-                    x,
-                    y,
-                    z,
-                    ..
-                }) => {
-                    if is_first {
-                        println!("print started at: {:?}, {:?}, {:?}", x, y, z);
-                        vertices.push(Vertex::at(
-                            x.unwrap_or(0.0),
-                            y.unwrap_or(0.0),
-                            z.unwrap_or(0.0),
-                        ));
-                        is_first = false;
-                        continue;
-                    }
-                    vertices.push(Vertex::at(
-                        x.unwrap_or(vertices.last().unwrap().x()),
-                        y.unwrap_or(vertices.last().unwrap().y()),
-                        z.unwrap_or(vertices.last().unwrap().z()),
-                    ));
-                },
-                _ => {},
-                
-                //println!("={:?}", command);
-            }
-        }
-        //println!("= {:?}", gcode::lexer::lex(line.as_str()));
+    let (toolpath, source) = options.open(&path).expect("Error opening file!");
+    if let Some(first) = toolpath.moves.first() {
+        println!("print started at: {:?}", first.end);
     }
-    //println!("{:?}", vertices);
 
     // initialize shaders and hook handlers
-    let app = Fig::init(&skeleton, vertices);
+    let app = Fig::init(&skeleton, path, toolpath, source, options, font);
 
     graphics::run::<Fig>(app, skeleton);
 }
@@ -0,0 +1,251 @@
+use graphics::data::Vertex;
+use wgpu::util::DeviceExt;
+
+use fig::retraction::{Analysis, Counts, Kind};
+use fig::seams::{Seam, Seams};
+use fig::tools::ToolChange;
+
+use crate::hud;
+use crate::text::{self, Align, Anchor, LabelId, Position};
+
+// half the width of a marker, mm
+const SIZE: f32 = 0.3;
+const VERTICES_PER_MARKER: u32 = 6;
+
+// the fragment shader entry for each kind of retraction marker, in
+// Kind::ALL order
+pub const RETRACTION_ENTRIES: [&str; 4] = ["fs_retract", "fs_unretract", "fs_zhop", "fs_wipe"];
+// external seams then internal ones
+pub const SEAM_ENTRIES: [&str; 2] = ["fs_seam_external", "fs_seam_internal"];
+// a color per extruder, T0 is the usual path color. past the end they
+// wrap around
+pub const TOOL_ENTRIES: [&str; 4] = ["fs_main", "fs_tool1", "fs_tool2", "fs_tool3"];
+
+// a little 3d cross at each of a bunch of points, in groups that are each
+// drawn in their own color
+pub struct Markers {
+    buffer: wgpu::Buffer,
+    // per kind, the first vertex and the move each marker belongs to so
+    // only the ones on visible layers get drawn
+    kinds: Vec<(u32, Vec<usize>)>,
+}
+
+impl Markers {
+    // every retraction, unretraction, z hop and wipe
+    pub fn retraction(device: &wgpu::Device, analysis: &Analysis) -> Self {
+        let kinds: Vec<Vec<(usize, [f32; 3])>> = Kind::ALL
+            .iter()
+            .map(|&kind| analysis.markers
+                .iter()
+                .filter(|marker| marker.kind == kind)
+                .map(|marker| (marker.index, marker.position))
+                .collect())
+            .collect();
+        Self::new(device, &kinds)
+    }
+
+    // where every perimeter loop starts
+    pub fn seams(device: &wgpu::Device, seams: &Seams) -> Self {
+        let kinds: Vec<Vec<(usize, [f32; 3])>> = [true, false]
+            .iter()
+            .map(|&external| seams.seams
+                .iter()
+                .filter(|seam| seam.external == external)
+                .map(|seam| (seam.moves.start, seam.start))
+                .collect())
+            .collect();
+        Self::new(device, &kinds)
+    }
+
+    // every switch to another extruder, in the color of the one its
+    // switching to. one before anything has moved has nowhere to go
+    pub fn tool_changes(device: &wgpu::Device, changes: &[ToolChange]) -> Self {
+        let kinds: Vec<Vec<(usize, [f32; 3])>> = (0..TOOL_ENTRIES.len())
+            .map(|color| changes
+                .iter()
+                .filter(|change| change.to % TOOL_ENTRIES.len() == color)
+                .filter_map(|change| Some((change.index, change.position?)))
+                .collect())
+            .collect();
+        Self::new(device, &kinds)
+    }
+
+    // each kind is a list of points along with the move they belong to, in
+    // order
+    fn new(device: &wgpu::Device, kinds: &[Vec<(usize, [f32; 3])>]) -> Self {
+        let mut vertices = Vec::new();
+        let kinds = kinds.iter().map(|points| {
+            let first = vertices.len() as u32;
+            for &(_, [x, y, z]) in points.iter() {
+                vertices.extend([
+                    Vertex::at(x - SIZE, y, z),
+                    Vertex::at(x + SIZE, y, z),
+                    Vertex::at(x, y - SIZE, z),
+                    Vertex::at(x, y + SIZE, z),
+                    Vertex::at(x, y, z - SIZE),
+                    Vertex::at(x, y, z + SIZE),
+                ]);
+            }
+            (first, points.iter().map(|&(index, _)| index).collect())
+        }).collect();
+        // wgpu doesnt like empty buffers
+        if vertices.is_empty() {
+            vertices.push(Vertex::at(0.0, 0.0, 0.0));
+        }
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Marker Buffer"),
+                contents: bytemuck::cast_slice(vertices.as_slice()),
+                usage: wgpu::BufferUsages::VERTEX,
+            }
+        );
+        Self { buffer, kinds }
+    }
+
+    // the markers for everything before the given move, one pipeline per
+    // kind. kinds without a pipeline arent drawn. the camera bind group
+    // should already be set
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, pipelines: &'a [wgpu::RenderPipeline], moves: usize) {
+        pass.set_vertex_buffer(0, self.buffer.slice(..));
+        for ((first, indices), pipeline) in self.kinds.iter().zip(pipelines) {
+            let count = indices.partition_point(|&i| i < moves) as u32;
+            if count > 0 {
+                pass.set_pipeline(pipeline);
+                pass.draw(*first..first + count * VERTICES_PER_MARKER, 0..1);
+            }
+        }
+    }
+}
+
+// counts and averages for the whole print and the current layer, in the
+// bottom right corner above the filament panel
+pub struct RetractionPanel {
+    pub panel: hud::Panel,
+}
+
+impl RetractionPanel {
+    pub fn new(text: &mut text::Layer) -> Self {
+        Self { panel: hud::Panel::new(text, Anchor::BottomRight) }
+    }
+
+    // call once per frame
+    pub fn update(&mut self, text: &mut text::Layer, analysis: &Analysis, layer: Option<usize>) {
+        if !self.panel.refresh(layer) {
+            return;
+        }
+
+        let layers = analysis.layers.len().max(1) as f32;
+        let counts = layer.and_then(|l| analysis.layers.get(l));
+        let rows: Vec<(&str, String)> = Kind::ALL
+            .iter()
+            .map(|&kind| (kind.name(), row(kind, &analysis.total, layers, counts)))
+            .collect();
+        self.panel.set_rows(text, &rows);
+    }
+}
+
+// 128  0.50mm avg  0.34/layer  this layer 2
+fn row(kind: Kind, total: &Counts, layers: f32, layer: Option<&Counts>) -> String {
+    let mut text = format!(
+        "{:>5}  {:.2}mm avg  {:.1}/layer",
+        total.count(kind),
+        total.average(kind),
+        total.count(kind) as f32 / layers,
+    );
+    if let Some(layer) = layer {
+        text += &format!("  this layer {}", layer.count(kind));
+    }
+    text
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SeamMode {
+    Off,
+    All,
+    External,
+}
+
+// the seams on the layer being looked at, in the bottom right corner
+// above the other panels
+pub struct SeamPanel {
+    pub panel: hud::Panel,
+    mode: SeamMode,
+    // next to each seam on the layer, up to SEAM_ROWS of them like the list
+    labels: Vec<LabelId>,
+}
+
+// past this the list gets cut short, its one row per loop
+const SEAM_ROWS: usize = 24;
+
+impl SeamPanel {
+    pub fn new(text: &mut text::Layer) -> Self {
+        Self { panel: hud::Panel::new(text, Anchor::BottomRight), mode: SeamMode::Off, labels: Vec::new() }
+    }
+
+    pub fn mode(&self) -> SeamMode {
+        self.mode
+    }
+
+    // all seams, then only external ones, then off
+    pub fn cycle(&mut self, text: &mut text::Layer) {
+        self.mode = match self.mode {
+            SeamMode::Off => SeamMode::All,
+            SeamMode::All => SeamMode::External,
+            SeamMode::External => SeamMode::Off,
+        };
+        self.panel.invalidate();
+        self.panel.set_visible(text, self.mode != SeamMode::Off);
+        if self.mode == SeamMode::Off {
+            self.clear_labels(text);
+        }
+    }
+
+    fn clear_labels(&mut self, text: &mut text::Layer) {
+        for label in self.labels.drain(..) {
+            text.remove(label);
+        }
+    }
+
+    // call once per frame
+    pub fn update(&mut self, text: &mut text::Layer, seams: &Seams, layer: Option<usize>) {
+        if !self.panel.refresh(layer) {
+            return;
+        }
+
+        let external = self.mode == SeamMode::External;
+        let shown = |seam: &&Seam| seam.external || !external;
+        let list: Vec<&Seam> = layer.map(|l| seams.layer(l)).unwrap_or(&[]).iter().filter(shown).collect();
+        let names: Vec<String> = list.iter().take(SEAM_ROWS).map(|seam| format!("line {}", seam.line)).collect();
+
+        let mut table = vec![("seams", format!("{} in print", seams.seams.iter().filter(shown).count()))];
+        if let Some(l) = layer {
+            table.push(("layer", format!("{}  {} seams", l + 1, list.len())));
+        }
+        for (name, seam) in names.iter().zip(list.iter()) {
+            let value = format!(
+                "{:.2}, {:.2}  {}{}",
+                seam.start[0],
+                seam.start[1],
+                if seam.external { "external" } else { "internal" },
+                if seam.closed() { "" } else { "  open" },
+            );
+            table.push((name, value));
+        }
+        if list.len() > SEAM_ROWS {
+            table.push(("", format!("{} more", list.len() - SEAM_ROWS)));
+        }
+        self.panel.set_rows(text, &table);
+
+        self.clear_labels(text);
+        // up and to the left of the seam, lined up against it
+        for (name, seam) in names.iter().zip(list.iter()) {
+            let name = if seam.closed() { name.clone() } else { format!("{}\nopen", name) };
+            self.labels.push(text.add(text::Label {
+                anchor: Anchor::BottomRight,
+                layout: text::Layout { align: Align::Right, ..Default::default() },
+                size: 12,
+                ..text::Label::new(&name, Position::World(seam.start))
+            }));
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use std::ops::Range;
+
+use crate::source::Source;
+use crate::toolpath::{self, Op, Toolpath};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Object {
+    pub name: String,
+    // the outline on the bed, from EXCLUDE_OBJECT_DEFINE or else around
+    // everything printed for it
+    pub polygon: Vec<[f32; 2]>,
+    pub center: [f32; 2],
+    // indices into Toolpath::moves, one range per time the printer came
+    // back to it
+    pub moves: Vec<Range<usize>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Objects {
+    pub objects: Vec<Object>,
+    // every range of moves with the object it belongs to, in file order
+    spans: Vec<(Range<usize>, usize)>,
+}
+
+impl Objects {
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    // which object a move belongs to, if any. skirts, purges and the
+    // travel between objects dont
+    pub fn at(&self, index: usize) -> Option<usize> {
+        let span = self.spans.partition_point(|(moves, _)| moves.end <= index);
+        self.spans
+            .get(span)
+            .filter(|(moves, _)| moves.contains(&index))
+            .map(|(_, object)| *object)
+    }
+
+    // the ranges of moves left to draw with some objects hidden, or with
+    // just one of them
+    pub fn shown(&self, hidden: &[bool], isolated: Option<usize>, moves: usize) -> Vec<Range<usize>> {
+        if let Some(object) = isolated {
+            return self.objects.get(object).map(|object| object.moves.clone()).unwrap_or_default();
+        }
+        let mut shown = Vec::new();
+        let mut start = 0;
+        for (span, object) in self.spans.iter() {
+            if hidden.get(*object).copied().unwrap_or(false) {
+                if start < span.start {
+                    shown.push(start..span.start);
+                }
+                start = span.end;
+            }
+        }
+        if start < moves {
+            shown.push(start..moves);
+        }
+        shown
+    }
+
+    fn index(&mut self, name: &str) -> usize {
+        match self.objects.iter().position(|object| object.name == name) {
+            Some(i) => i,
+            None => {
+                self.objects.push(Object { name: String::from(name), ..Object::default() });
+                self.objects.len() - 1
+            },
+        }
+    }
+}
+
+// klipper files say which object is which with EXCLUDE_OBJECT_START and
+// _END. slicers that dont (or arent set up to) still leave comments, which
+// get used instead. the names come from the source since ops dont carry
+// any text, which is also why anything a macro expanded into is skipped
+pub fn find(toolpath: &Toolpath, source: &Source) -> Objects {
+    let mut objects = Objects::default();
+    let events = || toolpath.events.iter().filter(|event| !event.expanded);
+    let klipper = events().any(|event| {
+        matches!(event.op, Op::Extended)
+            && matches!(exclude_object(source, event.line), Some((command, _)) if command == "START")
+    });
+
+    let mut current: Option<(usize, usize)> = None;
+    for event in events() {
+        let (start, name) = match event.op {
+            Op::Extended if klipper => {
+                let Some((command, text)) = exclude_object(source, event.line) else { continue };
+                let name = toolpath::params(&text).find(|(key, _)| key == "NAME").map(|(_, value)| String::from(value));
+                match (command.as_str(), name) {
+                    ("DEFINE", Some(name)) => {
+                        let object = objects.index(&name);
+                        define(&mut objects.objects[object], &text);
+                        continue;
+                    },
+                    ("START", Some(name)) => (true, name),
+                    ("END", name) => (false, name.unwrap_or_default()),
+                    _ => continue,
+                }
+            },
+            Op::Object { start } if !klipper => {
+                let Some(line) = source.line(event.line) else { continue };
+                (start, comment_name(&line))
+            },
+            _ => continue,
+        };
+        if let Some((object, first)) = current.take() {
+            if first < event.before {
+                objects.objects[object].moves.push(first..event.before);
+                objects.spans.push((first..event.before, object));
+            }
+        }
+        if start {
+            current = Some((objects.index(&name), event.before));
+        }
+    }
+    if let Some((object, first)) = current {
+        let end = toolpath.moves.len();
+        if first < end {
+            objects.objects[object].moves.push(first..end);
+            objects.spans.push((first..end, object));
+        }
+    }
+
+    for object in objects.objects.iter_mut() {
+        if object.polygon.is_empty() {
+            let points: Vec<[f32; 2]> = object.moves
+                .iter()
+                .flat_map(|range| toolpath.moves[range.clone()].iter())
+                .filter(|m| m.is_extrusion())
+                .flat_map(|m| [[m.start[0], m.start[1]], [m.end[0], m.end[1]]])
+                .collect();
+            object.polygon = hull(points);
+            object.center = middle(&object.polygon);
+        }
+    }
+    objects
+}
+
+// EXCLUDE_OBJECT_DEFINE NAME=a.stl CENTER=1,2 ... gives ("DEFINE", the
+// line without its comment)
+fn exclude_object(source: &Source, line: u32) -> Option<(String, String)> {
+    let line = source.line(line)?;
+    let code = toolpath::strip_comment(&line);
+    let command = code.split_whitespace().next()?.to_ascii_uppercase();
+    let command = command.strip_prefix("EXCLUDE_OBJECT_")?;
+    Some((String::from(command), String::from(code)))
+}
+
+// `; printing object a.stl id:0 copy 0` and `;MESH:a.stl`. the stop comment
+// and ;MESH:NONMESH end whatever was started so their names dont matter
+fn comment_name(line: &str) -> String {
+    let comment = line.trim_start().trim_start_matches(';').trim();
+    let name = comment
+        .strip_prefix("stop printing object")
+        .or_else(|| comment.strip_prefix("printing object"))
+        .or_else(|| comment.strip_prefix("MESH:"))
+        .unwrap_or(comment);
+    String::from(name.trim())
+}
+
+// CENTER=150.0,150.0 POLYGON=[[140,140],[160,140],[160,160],[140,160]]
+fn define(object: &mut Object, text: &str) {
+    // kept apart from the polygon so a CENTER=0,0 thats really there isnt
+    // taken for a missing one
+    let mut center = None;
+    for (key, value) in toolpath::params(text) {
+        match key.as_str() {
+            "CENTER" => center = point(value).or(center),
+            "POLYGON" => {
+                let value = value.trim().trim_start_matches('[').trim_end_matches(']');
+                object.polygon = value.split("],[").filter_map(point).collect();
+            },
+            _ => {},
+        }
+    }
+    match center {
+        Some(center) => object.center = center,
+        None if !object.polygon.is_empty() => object.center = middle(&object.polygon),
+        None => {},
+    }
+}
+
+fn point(text: &str) -> Option<[f32; 2]> {
+    let (x, y) = text.split_once(',')?;
+    Some([x.trim().parse().ok()?, y.trim().parse().ok()?])
+}
+
+// the middle of the box around it
+fn middle(polygon: &[[f32; 2]]) -> [f32; 2] {
+    let Some(first) = polygon.first() else {
+        return [0.0; 2];
+    };
+    let (mut min, mut max) = (*first, *first);
+    for point in polygon.iter() {
+        for axis in 0..2 {
+            min[axis] = min[axis].min(point[axis]);
+            max[axis] = max[axis].max(point[axis]);
+        }
+    }
+    [(min[0] + max[0]) * 0.5, (min[1] + max[1]) * 0.5]
+}
+
+// andrews monotone chain, counter clockwise
+fn hull(mut points: Vec<[f32; 2]>) -> Vec<[f32; 2]> {
+    points.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut lower = half_hull(points.iter());
+    let mut upper = half_hull(points.iter().rev());
+    // each ends where the other starts
+    lower.pop();
+    upper.pop();
+    lower.extend(upper);
+    lower
+}
+
+fn half_hull<'a>(points: impl Iterator<Item = &'a [f32; 2]>) -> Vec<[f32; 2]> {
+    let cross = |o: [f32; 2], a: [f32; 2], b: [f32; 2]| (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0]);
+    let mut half: Vec<[f32; 2]> = Vec::new();
+    for &point in points {
+        while half.len() >= 2 && cross(half[half.len() - 2], half[half.len() - 1], point) <= 0.0 {
+            half.pop();
+        }
+        half.push(point);
+    }
+    half
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::tests::{load_text, load_text_with};
+    use crate::macros::Macros;
+
+    #[test]
+    fn exclude_object_wins_over_comments() {
+        let text = "\
+EXCLUDE_OBJECT_DEFINE NAME=cube CENTER=15,15 POLYGON=[[10,10],[20,10],[20,20],[10,20]]
+EXCLUDE_OBJECT_DEFINE NAME=cone POLYGON=[[30,10],[40,10],[40,20]]
+M83
+G1 Z0.2 F3000
+G1 X10 Y10
+EXCLUDE_OBJECT_START NAME=cube
+; printing object cube.stl id:0 copy 0
+G1 X20 Y10 E1
+EXCLUDE_OBJECT_END NAME=cube
+G1 X30 Y10
+EXCLUDE_OBJECT_START NAME=cone
+G1 X40 Y10 E1
+G1 X40 Y20 E1
+EXCLUDE_OBJECT_END NAME=cone
+G1 Z0.4
+EXCLUDE_OBJECT_START NAME=cube
+G1 X20 Y20 E1
+";
+        let (toolpath, source) = load_text(text);
+        let objects = find(&toolpath, &source);
+        assert_eq!(
+            objects.objects,
+            vec![
+                Object {
+                    name: String::from("cube"),
+                    polygon: vec![[10.0, 10.0], [20.0, 10.0], [20.0, 20.0], [10.0, 20.0]],
+                    center: [15.0, 15.0],
+                    moves: vec![2..3, 7..8],
+                },
+                Object {
+                    name: String::from("cone"),
+                    polygon: vec![[30.0, 10.0], [40.0, 10.0], [40.0, 20.0]],
+                    center: [35.0, 15.0],
+                    moves: vec![Range { start: 4, end: 6 }],
+                },
+            ],
+        );
+        assert_eq!([1, 2, 3, 4, 7].map(|i| objects.at(i)), [None, Some(0), None, Some(1), Some(0)]);
+        assert_eq!(objects.shown(&[true, false], None, 8), vec![0..2, 3..7]);
+        assert_eq!(objects.shown(&[false, false], Some(0), 8), vec![2..3, 7..8]);
+    }
+
+    #[test]
+    fn define_keeps_a_center_at_the_origin() {
+        let mut object = Object { name: String::from("cube"), polygon: Vec::new(), center: [0.0; 2], moves: Vec::new() };
+        define(&mut object, "EXCLUDE_OBJECT_DEFINE NAME=cube CENTER=0,0 POLYGON=[[10,10],[20,10],[20,20]]");
+        assert_eq!(object.center, [0.0, 0.0]);
+        define(&mut object, "EXCLUDE_OBJECT_DEFINE NAME=cube POLYGON=[[10,10],[20,10],[20,20]]");
+        assert_eq!(object.center, [15.0, 15.0]);
+    }
+
+    #[test]
+    fn comments_when_there_is_no_exclude_object() {
+        let text = "\
+M83
+G1 Z0.2 F3000
+; printing object a.stl id:0 copy 0
+G1 X10 Y10
+G1 X20 Y10 E1
+G1 X20 Y20 E1
+; stop printing object a.stl id:0 copy 0
+;MESH:b.stl
+G1 X30 Y30 E1
+;MESH:NONMESH
+G1 X0 Y0
+";
+        let (toolpath, source) = load_text(text);
+        let objects = find(&toolpath, &source);
+        assert_eq!(
+            objects.objects,
+            vec![
+                Object {
+                    name: String::from("a.stl id:0 copy 0"),
+                    polygon: vec![[10.0, 10.0], [20.0, 10.0], [20.0, 20.0]],
+                    center: [15.0, 15.0],
+                    moves: vec![Range { start: 1, end: 4 }],
+                },
+                Object {
+                    name: String::from("b.stl"),
+                    polygon: vec![[20.0, 20.0], [30.0, 30.0]],
+                    center: [25.0, 25.0],
+                    moves: vec![Range { start: 4, end: 5 }],
+                },
+            ],
+        );
+        assert_eq!(objects.at(5), None);
+    }
+
+    #[test]
+    fn objects_a_macro_expanded_into_are_skipped() {
+        let macros = Macros::parse("[gcode_macro PURGE]\ngcode:\n    EXCLUDE_OBJECT_START NAME=purge\n    G1 X5 Y5 E5\n");
+        let text = "M83\nG1 Z0.2 F3000\nPURGE\n; printing object a.stl\nG1 X10 Y10 E1\n";
+        let (toolpath, source) = load_text_with(text, &macros);
+        assert!(toolpath.events.iter().any(|event| event.expanded));
+        let names: Vec<String> = find(&toolpath, &source).objects.into_iter().map(|object| object.name).collect();
+        assert_eq!(names, vec![String::from("a.stl")]);
+    }
+}
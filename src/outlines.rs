@@ -0,0 +1,215 @@
+use graphics::data::Vertex;
+use wgpu::util::DeviceExt;
+
+use fig::objects::Objects;
+
+use crate::hud;
+use crate::text::{self, Align, Anchor, LabelId, Position};
+
+// half the width of the cross at each center, mm
+const CENTER_SIZE: f32 = 2.0;
+// names past this get cut short
+const NAME_LENGTH: usize = 32;
+// names on the bed wrap past this, logical pixels
+const LABEL_WIDTH: f32 = 120.0;
+
+// outlines, drawn like the build volume, and the selected one like the
+// selection
+pub const FRAGMENT_ENTRIES: [&str; 2] = ["fs_volume", "fs_highlight"];
+
+// each objects outline and a cross at its center, flat on the bed
+pub struct Outlines {
+    buffer: wgpu::Buffer,
+    // the vertices for each object
+    objects: Vec<std::ops::Range<u32>>,
+}
+
+impl Outlines {
+    pub fn new(device: &wgpu::Device, objects: &Objects) -> Self {
+        let mut vertices = Vec::new();
+        let mut ranges = Vec::new();
+        for object in objects.objects.iter() {
+            let first = vertices.len() as u32;
+            let polygon = &object.polygon;
+            for (i, [x, y]) in polygon.iter().copied().enumerate() {
+                let [next_x, next_y] = polygon[(i + 1) % polygon.len()];
+                vertices.extend([Vertex::at(x, y, 0.0), Vertex::at(next_x, next_y, 0.0)]);
+            }
+            let [x, y] = object.center;
+            vertices.extend([
+                Vertex::at(x - CENTER_SIZE, y, 0.0),
+                Vertex::at(x + CENTER_SIZE, y, 0.0),
+                Vertex::at(x, y - CENTER_SIZE, 0.0),
+                Vertex::at(x, y + CENTER_SIZE, 0.0),
+            ]);
+            ranges.push(first..vertices.len() as u32);
+        }
+        // wgpu doesnt like empty buffers
+        if vertices.is_empty() {
+            vertices.push(Vertex::at(0.0, 0.0, 0.0));
+        }
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Outline Buffer"),
+                contents: bytemuck::cast_slice(vertices.as_slice()),
+                usage: wgpu::BufferUsages::VERTEX,
+            }
+        );
+        Self { buffer, objects: ranges }
+    }
+
+    // pipelines in FRAGMENT_ENTRIES order. the camera bind group should
+    // already be set
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, pipelines: &'a [wgpu::RenderPipeline], selected: Option<usize>) {
+        pass.set_vertex_buffer(0, self.buffer.slice(..));
+        pass.set_pipeline(&pipelines[0]);
+        for (i, vertices) in self.objects.iter().enumerate() {
+            if Some(i) != selected {
+                pass.draw(vertices.clone(), 0..1);
+            }
+        }
+        if let Some(vertices) = selected.and_then(|i| self.objects.get(i)) {
+            pass.set_pipeline(&pipelines[1]);
+            pass.draw(vertices.clone(), 0..1);
+        }
+    }
+}
+
+// the list of objects in the bottom right corner, along with which one is
+// selected and which are hidden
+pub struct ObjectPanel {
+    pub panel: hud::Panel,
+    selected: Option<usize>,
+    hidden: Vec<bool>,
+    isolated: Option<usize>,
+    // each shown objects name over its center, while the list is open
+    labels: Vec<LabelId>,
+}
+
+impl ObjectPanel {
+    pub fn new(text: &mut text::Layer, count: usize) -> Self {
+        Self {
+            panel: hud::Panel::new(text, Anchor::BottomRight),
+            selected: None,
+            hidden: vec![false; count],
+            isolated: None,
+            labels: Vec::new(),
+        }
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn hidden(&self) -> &[bool] {
+        &self.hidden
+    }
+
+    pub fn isolated(&self) -> Option<usize> {
+        self.isolated
+    }
+
+    pub fn toggle(&mut self, text: &mut text::Layer) {
+        self.panel.toggle(text);
+        // built again when its next shown
+        self.clear_labels(text);
+        self.panel.invalidate();
+    }
+
+    fn clear_labels(&mut self, text: &mut text::Layer) {
+        for label in self.labels.drain(..) {
+            text.remove(label);
+        }
+    }
+
+    // a different file, everything goes back to being shown
+    pub fn reset(&mut self, text: &mut text::Layer, count: usize) {
+        self.clear_labels(text);
+        self.selected = None;
+        self.hidden = vec![false; count];
+        self.isolated = None;
+        self.panel.invalidate();
+    }
+
+    // steps through the objects and then back to none
+    pub fn select_next(&mut self) {
+        self.selected = match self.selected {
+            None if !self.hidden.is_empty() => Some(0),
+            Some(i) if i + 1 < self.hidden.len() => Some(i + 1),
+            _ => None,
+        };
+        self.panel.invalidate();
+    }
+
+    // these return whether what gets drawn changed
+    pub fn toggle_hidden(&mut self) -> bool {
+        let Some(hidden) = self.selected.and_then(|i| self.hidden.get_mut(i)) else {
+            return false;
+        };
+        *hidden = !*hidden;
+        self.panel.invalidate();
+        true
+    }
+
+    pub fn toggle_isolated(&mut self) -> bool {
+        let Some(selected) = self.selected else {
+            return false;
+        };
+        self.isolated = if self.isolated == Some(selected) { None } else { Some(selected) };
+        self.panel.invalidate();
+        true
+    }
+
+    // call once per frame. it doesnt change from layer to layer
+    pub fn update(&mut self, text: &mut text::Layer, objects: &Objects) {
+        if !self.panel.refresh(None) {
+            return;
+        }
+        self.clear_labels(text);
+
+        if objects.is_empty() {
+            self.panel.set_rows(text, &[("objects", String::from("none labelled"))]);
+            return;
+        }
+        let names: Vec<String> = objects.objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| {
+                let mark = if Some(i) == self.selected { ">" } else { " " };
+                format!("{} {}", mark, object.name.chars().take(NAME_LENGTH).collect::<String>())
+            })
+            .collect();
+        let rows: Vec<(&str, String)> = names
+            .iter()
+            .zip(objects.objects.iter())
+            .enumerate()
+            .map(|(i, (name, object))| {
+                let moves: usize = object.moves.iter().map(|range| range.len()).sum();
+                let state = if self.isolated == Some(i) {
+                    "  isolated"
+                } else if self.hidden.get(i).copied().unwrap_or(false) {
+                    "  hidden"
+                } else {
+                    ""
+                };
+                (name.as_str(), format!("{} moves{}", moves, state))
+            })
+            .collect();
+        self.panel.set_rows(text, &rows);
+
+        for (i, object) in objects.objects.iter().enumerate() {
+            let hidden = self.hidden.get(i).copied().unwrap_or(false);
+            if self.isolated.map_or(hidden, |isolated| isolated != i) {
+                continue;
+            }
+            let name: String = object.name.chars().take(NAME_LENGTH).collect();
+            let [x, y] = object.center;
+            self.labels.push(text.add(text::Label {
+                anchor: Anchor::Center,
+                layout: text::Layout { align: Align::Center, max_width: Some(LABEL_WIDTH) },
+                size: 12,
+                ..text::Label::new(&name, Position::World([x, y, 0.0]))
+            }));
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use fig::source::Source;
+
+use crate::text::{self, Anchor, LabelId, Position, Span, Style};
+
+// lines shown either side of the current one
+const CONTEXT: u32 = 12;
+// longer lines get cut off, thumbnails and the like are one enormous line
+const MAX_COLUMNS: usize = 64;
+
+const NUMBER: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+const CODE: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
+const COMMENT: [f32; 4] = [0.4, 0.6, 0.4, 1.0];
+const CURRENT: [f32; 4] = [1.0, 1.0, 0.4, 1.0];
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const HIGHLIGHT: [f32; 4] = [0.25, 0.25, 0.1, 0.8];
+
+// the gcode around the current line, down the right hand side. the
+// current line is what gets highlighted in the 3d view
+pub struct SourcePanel {
+    label: LabelId,
+    source: Source,
+    // 1 based
+    line: u32,
+    visible: bool,
+    dirty: bool,
+}
+
+impl SourcePanel {
+    pub fn new(text: &mut text::Layer, source: Source) -> Self {
+        let label = text.add(text::Label {
+            anchor: Anchor::TopRight,
+            size: 13,
+            visible: false,
+            ..text::Label::new("", Position::Screen([8.0, 8.0]))
+        });
+        Self {
+            label,
+            source,
+            line: 1,
+            visible: false,
+            dirty: true,
+        }
+    }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self, text: &mut text::Layer) {
+        self.visible = !self.visible;
+        if let Some(label) = text.get_mut(self.label) {
+            label.visible = self.visible;
+        }
+    }
+
+    // new file. reloads of the same one keep their place
+    pub fn set_source(&mut self, source: Source, reload: bool) {
+        self.source = source;
+        self.set_line(if reload { self.line } else { 1 });
+        self.dirty = true;
+    }
+
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    pub fn set_line(&mut self, line: u32) {
+        let line = line.clamp(1, self.source.len().max(1));
+        if line != self.line {
+            self.line = line;
+            self.dirty = true;
+        }
+    }
+
+    pub fn scroll(&mut self, lines: i32) {
+        self.set_line(self.line.saturating_add_signed(lines));
+    }
+
+    pub fn update(&mut self, text: &mut text::Layer) {
+        if !self.visible || !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        text.set_spans(self.label, &self.spans());
+    }
+
+    fn spans(&self) -> Vec<Span> {
+        let first = self.line.saturating_sub(CONTEXT).max(1);
+        let last = (self.line + CONTEXT).min(self.source.len());
+        let digits = last.max(1).to_string().len();
+
+        let mut spans = Vec::new();
+        for number in first..=last {
+            let Some(line) = self.source.line(number) else { break };
+            let current = number == self.line;
+            let background = Some(if current { HIGHLIGHT } else { BACKGROUND });
+            let style = |color| Style { background, ..Style::color(color) };
+
+            if number > first {
+                spans.push(Span::new("\n", style(CODE)));
+            }
+            spans.push(Span::new(&format!(" {:>digits$} ", number, digits = digits), style(NUMBER)));
+
+            // tabs would throw the padding out
+            let line: String = line.replace('\t', " ").chars().take(MAX_COLUMNS).collect();
+            let (code, comment) = match line.find(';') {
+                Some(i) => line.split_at(i),
+                None => (line.as_str(), ""),
+            };
+            let code_color = if current { CURRENT } else { CODE };
+            spans.push(Span::new(code, style(code_color)));
+            spans.push(Span::new(comment, style(COMMENT)));
+            // pad out so the backgrounds make a box
+            let padding = MAX_COLUMNS - line.chars().count() + 1;
+            spans.push(Span::new(&" ".repeat(padding), style(CODE)));
+        }
+        spans
+    }
+}
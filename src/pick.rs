@@ -0,0 +1,118 @@
+use std::ops::Range;
+
+use fig::source::Source;
+use fig::toolpath::{Layer, Move};
+
+use crate::hud;
+use crate::text::{self, Anchor, LabelId, Position};
+
+// how close the cursor has to be to a segment to pick it, in pixels
+pub const RADIUS: f32 = 8.0;
+
+// finds the segment drawn nearest to a point on the screen, going by
+// where the segments end up on screen rather than a ray through the scene
+// so thin lines are as easy to hit as thick ones. there is no depth test
+// on the path, later moves draw over earlier ones, so ties go to the later
+// move. only the moves in ranges are looked at, which have to be in file
+// order. cursor and screen are in pixels from the bottom left
+pub fn pick(
+    moves: &[Move],
+    ranges: &[Range<usize>],
+    view_proj: cgmath::Matrix4<f32>,
+    screen: [f32; 2],
+    cursor: [f32; 2],
+) -> Option<usize> {
+    let mut best = None;
+    let mut best_distance = RADIUS;
+    for i in ranges.iter().flat_map(|range| range.clone()) {
+        let Some(m) = moves.get(i) else { continue };
+        let (Some(a), Some(b)) = (
+            text::project(view_proj, m.start, screen),
+            text::project(view_proj, m.end, screen),
+        ) else {
+            continue;
+        };
+        let distance = distance_to_segment(cursor, a, b);
+        if distance <= best_distance {
+            best_distance = distance;
+            best = Some(i);
+        }
+    }
+    best
+}
+
+fn distance_to_segment(p: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let ab = [b[0] - a[0], b[1] - a[1]];
+    let ap = [p[0] - a[0], p[1] - a[1]];
+    let length = ab[0] * ab[0] + ab[1] * ab[1];
+    let t = if length > 0.0 {
+        ((ap[0] * ab[0] + ap[1] * ab[1]) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let closest = [a[0] + ab[0] * t, a[1] + ab[1] * t];
+    ((p[0] - closest[0]).powi(2) + (p[1] - closest[1]).powi(2)).sqrt()
+}
+
+// the properties of whatever was picked, in the bottom left corner
+pub struct Inspector {
+    label: LabelId,
+}
+
+impl Inspector {
+    pub fn new(text: &mut text::Layer) -> Self {
+        let label = text.add(text::Label {
+            anchor: Anchor::BottomLeft,
+            size: 14,
+            visible: false,
+            ..text::Label::new("", Position::Screen([8.0, 8.0]))
+        });
+        Self { label }
+    }
+
+    pub fn label(&self) -> LabelId {
+        self.label
+    }
+
+    pub fn show(&mut self, text: &mut text::Layer, selected: Option<(usize, &Move)>, layers: &[Layer], source: &Source) {
+        let Some((i, m)) = selected else {
+            if let Some(label) = text.get_mut(self.label) {
+                label.visible = false;
+            }
+            return;
+        };
+
+        // thumbnails and such are one enormous line
+        let command: String = source.line(m.line)
+            .unwrap_or_default()
+            .trim()
+            .chars()
+            .take(48)
+            .collect();
+        let index = layers.partition_point(|layer| layer.moves.end <= i);
+        let layer = match layers.get(index) {
+            Some(layer) => format!("{} / {}  z {:.2}", index + 1, layers.len(), layer.z),
+            None => String::from("-"),
+        };
+        let size = if m.width > 0.0 || m.height > 0.0 {
+            format!("{:.2} x {:.2}mm", m.width, m.height)
+        } else {
+            String::from("-")
+        };
+        let rows = [
+            ("line", m.line.to_string()),
+            ("code", command),
+            ("type", String::from(if m.is_extrusion() { m.feature.name() } else { "travel" })),
+            ("tool", format!("T{}", m.tool)),
+            ("speed", format!("{:.1}mm/s", m.feedrate / 60.0)),
+            ("e", format!("{:.5}mm", m.e)),
+            ("size", size),
+            ("layer", layer),
+        ];
+
+        text.set_spans(self.label, &hud::table(&rows));
+        if let Some(label) = text.get_mut(self.label) {
+            label.visible = true;
+        }
+    }
+}
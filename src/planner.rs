@@ -0,0 +1,524 @@
+use crate::toolpath::{Event, Layer, Move, Op, Toolpath};
+
+// how corners are taken. marlin does classic jerk or junction deviation
+// depending on how its built, klipper always goes by a square corner
+// velocity
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Cornering {
+    // the most any one axis (x, y, z, e) can change speed instantly, mm/s
+    Jerk([f32; 4]),
+    // mm
+    JunctionDeviation(f32),
+    // mm/s through a 90 degree corner
+    SquareCornerVelocity(f32),
+}
+
+// everything the firmware limits moves by. the file can change all of it
+// as it goes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Limits {
+    // x, y, z, e in mm/s
+    pub max_feedrate: [f32; 4],
+    // x, y, z, e in mm/s^2
+    pub max_acceleration: [f32; 4],
+    // mm/s^2
+    pub print_acceleration: f32,
+    pub travel_acceleration: f32,
+    pub retract_acceleration: f32,
+    // klippers overall caps, on top of the per axis ones
+    pub max_velocity: f32,
+    pub accel_to_decel: Option<f32>,
+    pub cornering: Cornering,
+}
+
+impl Default for Limits {
+    // marlins defaults
+    fn default() -> Self {
+        Self {
+            max_feedrate: [300.0, 300.0, 5.0, 25.0],
+            max_acceleration: [3000.0, 3000.0, 100.0, 10000.0],
+            print_acceleration: 3000.0,
+            travel_acceleration: 3000.0,
+            retract_acceleration: 3000.0,
+            max_velocity: f32::INFINITY,
+            accel_to_decel: None,
+            cornering: Cornering::Jerk([10.0, 10.0, 0.3, 5.0]),
+        }
+    }
+}
+
+impl Limits {
+    // klipper has no defaults for most of these, printer.cfg has to set
+    // them. these are what its example configs usually go with, and z from
+    // the ender 3 one. it has no per axis limits on x and y
+    pub fn klipper() -> Self {
+        Self {
+            max_feedrate: [f32::INFINITY, f32::INFINITY, 5.0, 25.0],
+            max_acceleration: [f32::INFINITY, f32::INFINITY, 100.0, 10000.0],
+            print_acceleration: 3000.0,
+            travel_acceleration: 3000.0,
+            retract_acceleration: 3000.0,
+            max_velocity: 300.0,
+            // half of max_accel unless its set
+            accel_to_decel: Some(1500.0),
+            cornering: Cornering::SquareCornerVelocity(5.0),
+        }
+    }
+
+    // the defaults of whichever firmware the file is for. klipper if the
+    // slicer was set up for it or the file uses its commands, otherwise
+    // marlin
+    pub fn for_toolpath(toolpath: &Toolpath) -> Self {
+        let klipper = toolpath.config.get("gcode_flavor") == Some("klipper")
+            || toolpath.events.iter().any(|event| matches!(event.op, Op::Extended | Op::VelocityLimit { .. }));
+        if klipper {
+            Self::klipper()
+        } else {
+            Self::default()
+        }
+    }
+
+    // folds in anything the op changes
+    pub fn apply(&mut self, op: &Op) {
+        match *op {
+            Op::Acceleration { print, travel, retract } => {
+                set(&mut self.print_acceleration, print);
+                set(&mut self.travel_acceleration, travel);
+                set(&mut self.retract_acceleration, retract);
+            },
+            Op::Jerk { x, y, z, e, junction_deviation } => {
+                if let Some(deviation) = junction_deviation {
+                    self.cornering = Cornering::JunctionDeviation(deviation);
+                } else if x.is_some() || y.is_some() || z.is_some() || e.is_some() {
+                    let mut jerk = match self.cornering {
+                        Cornering::Jerk(jerk) => jerk,
+                        _ => Limits::default().jerk(),
+                    };
+                    for (axis, value) in [x, y, z, e].into_iter().enumerate() {
+                        set(&mut jerk[axis], value);
+                    }
+                    self.cornering = Cornering::Jerk(jerk);
+                }
+            },
+            Op::MaxFeedrate(axes) => {
+                for (axis, value) in axes.into_iter().enumerate() {
+                    set(&mut self.max_feedrate[axis], value);
+                }
+            },
+            Op::MaxAcceleration(axes) => {
+                for (axis, value) in axes.into_iter().enumerate() {
+                    set(&mut self.max_acceleration[axis], value);
+                }
+            },
+            Op::VelocityLimit { velocity, accel, accel_to_decel, square_corner_velocity } => {
+                set(&mut self.max_velocity, velocity);
+                // klipper has the one acceleration for everything
+                set(&mut self.print_acceleration, accel);
+                set(&mut self.travel_acceleration, accel);
+                set(&mut self.retract_acceleration, accel);
+                if accel_to_decel.is_some() {
+                    self.accel_to_decel = accel_to_decel;
+                }
+                if let Some(velocity) = square_corner_velocity {
+                    self.cornering = Cornering::SquareCornerVelocity(velocity);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn jerk(&self) -> [f32; 4] {
+        match self.cornering {
+            Cornering::Jerk(jerk) => jerk,
+            _ => [f32::INFINITY; 4],
+        }
+    }
+}
+
+fn set(value: &mut f32, new: Option<f32>) {
+    if let Some(new) = new {
+        *value = new;
+    }
+}
+
+// a move the way the planner sees it
+#[derive(Copy, Clone, Debug)]
+struct Block {
+    // mm, or the length of filament for moves that only turn the extruder
+    length: f32,
+    // direction in x, y, z, e. normalized over xyz unless its e only
+    unit: [f32; 4],
+    // mm/s
+    nominal: f32,
+    // mm/s^2
+    acceleration: f32,
+    accel_to_decel: f32,
+    // fastest the move can start at, from the corner it comes out of
+    max_entry: f32,
+    // seconds of G4 before this move
+    dwell: f32,
+}
+
+impl Block {
+    fn new(m: &Move, limits: &Limits) -> Option<Self> {
+        let delta = [
+            m.end[0] - m.start[0],
+            m.end[1] - m.start[1],
+            m.end[2] - m.start[2],
+            m.e,
+        ];
+        let xyz = m.length();
+        let length = if xyz > 0.0 { xyz } else { m.e.abs() };
+        if length <= 0.0 || m.feedrate <= 0.0 {
+            return None;
+        }
+        let unit = delta.map(|d| d / length);
+
+        // slow the whole move down until no axis is over its limits
+        let mut nominal = (m.feedrate / 60.0).min(limits.max_velocity);
+        let mut acceleration = if xyz == 0.0 {
+            limits.retract_acceleration
+        } else if m.e > 0.0 {
+            limits.print_acceleration
+        } else {
+            limits.travel_acceleration
+        };
+        for ((share, max_feedrate), max_acceleration) in unit.iter().zip(limits.max_feedrate).zip(limits.max_acceleration) {
+            let share = share.abs();
+            if share > 0.0 {
+                nominal = nominal.min(max_feedrate / share);
+                acceleration = acceleration.min(max_acceleration / share);
+            }
+        }
+        let accel_to_decel = limits.accel_to_decel.unwrap_or(acceleration).min(acceleration);
+
+        Some(Self {
+            length,
+            unit,
+            nominal,
+            acceleration,
+            accel_to_decel,
+            max_entry: 0.0,
+            dwell: 0.0,
+        })
+    }
+
+    fn is_extruder_only(&self) -> bool {
+        self.unit[0] == 0.0 && self.unit[1] == 0.0 && self.unit[2] == 0.0
+    }
+
+    // fastest it can start or end from a standstill
+    fn safe_speed(&self, limits: &Limits) -> f32 {
+        let mut speed = self.nominal;
+        if let Cornering::Jerk(jerk) = limits.cornering {
+            for (share, jerk) in self.unit.iter().zip(jerk) {
+                let share = share.abs();
+                if share > 0.0 {
+                    speed = speed.min(jerk / share);
+                }
+            }
+            speed
+        } else {
+            0.0
+        }
+    }
+
+    // how long it takes going in at entry and out at exit
+    fn time(&self, entry: f32, exit: f32) -> f32 {
+        let a = self.acceleration;
+        if a <= 0.0 {
+            return self.length / self.nominal;
+        }
+        // the highest speed reachable before having to slow down again.
+        // klipper caps this harder with accel_to_decel
+        let peak_squared = ((2.0 * a * self.length + entry * entry + exit * exit) * 0.5)
+            .min((2.0 * self.accel_to_decel * self.length + entry * entry + exit * exit) * 0.5);
+        let cruise = self.nominal.min(peak_squared.sqrt()).max(entry).max(exit);
+        let accelerate = (cruise * cruise - entry * entry) / (2.0 * a);
+        let decelerate = (cruise * cruise - exit * exit) / (2.0 * a);
+        let coast = (self.length - accelerate - decelerate).max(0.0);
+        let coast_time = if cruise > 0.0 { coast / cruise } else { 0.0 };
+        (cruise - entry) / a + (cruise - exit) / a + coast_time
+    }
+}
+
+// fastest the corner from previous into next can be taken
+fn junction_speed(previous: &Block, next: &Block, limits: &Limits) -> f32 {
+    // retractions and the like, treated the same as starting from a stop
+    if previous.is_extruder_only() || next.is_extruder_only() {
+        return previous.safe_speed(limits).min(next.safe_speed(limits));
+    }
+    let max = previous.nominal.min(next.nominal);
+    match limits.cornering {
+        Cornering::Jerk(jerk) => {
+            // marlin lets each axis jump by up to its jerk
+            let mut speed = max;
+            for (axis, jerk) in jerk.into_iter().enumerate() {
+                let jump = (next.unit[axis] - previous.unit[axis]).abs() * speed;
+                if jump > jerk {
+                    speed *= jerk / jump;
+                }
+            }
+            speed
+        },
+        Cornering::JunctionDeviation(deviation) => deviation_speed(previous, next, deviation, next.acceleration).min(max),
+        Cornering::SquareCornerVelocity(velocity) => {
+            // klippers conversion from square corner velocity
+            let deviation = velocity * velocity * (std::f32::consts::SQRT_2 - 1.0) / next.acceleration;
+            deviation_speed(previous, next, deviation, next.acceleration).min(max)
+        },
+    }
+}
+
+// the speed an arc through the corner that stays within deviation of it
+// can be taken at
+fn deviation_speed(previous: &Block, next: &Block, deviation: f32, acceleration: f32) -> f32 {
+    let cos_theta = -(0..3).map(|axis| previous.unit[axis] * next.unit[axis]).sum::<f32>();
+    if cos_theta > 0.999999 {
+        // a full reversal
+        return 0.0;
+    }
+    if cos_theta < -0.999999 {
+        // straight on
+        return f32::INFINITY;
+    }
+    let sin_half = (0.5 * (1.0 - cos_theta)).sqrt();
+    (acceleration * deviation * sin_half / (1.0 - sin_half)).sqrt()
+}
+
+// per move times, in seconds
+pub struct Estimate {
+    pub moves: Vec<f32>,
+    pub total: f32,
+}
+
+impl Estimate {
+    pub fn layer(&self, layer: &Layer) -> f32 {
+        self.moves[layer.moves.clone()].iter().sum()
+    }
+
+    // seconds from the start to the beginning of the given move
+    pub fn elapsed(&self, before: usize) -> f32 {
+        self.moves[..before.min(self.moves.len())].iter().sum()
+    }
+}
+
+// runs the toolpath through a model of the firmwares motion planner:
+// trapezoidal speed profiles with the junction speeds between moves worked
+// out from the cornering model, then planned backwards and forwards over
+// the whole print so every move can actually stop in time
+pub fn estimate(toolpath: &Toolpath, mut limits: Limits) -> Estimate {
+    let mut events = toolpath.events.iter().peekable();
+    let mut blocks: Vec<Option<Block>> = Vec::with_capacity(toolpath.moves.len());
+    let mut dwell = 0.0;
+    let mut previous: Option<Block> = None;
+    for (i, m) in toolpath.moves.iter().enumerate() {
+        while let Some(Event { op, .. }) = events.next_if(|event| event.before <= i) {
+            if let Op::Dwell(seconds) = op {
+                dwell += seconds;
+            }
+            limits.apply(op);
+        }
+
+        let Some(mut block) = Block::new(m, &limits) else {
+            blocks.push(None);
+            continue;
+        };
+        block.dwell = std::mem::take(&mut dwell);
+        block.max_entry = match previous {
+            // the machine comes to a stop for a dwell
+            _ if block.dwell > 0.0 => 0.0,
+            Some(previous) => junction_speed(&previous, &block, &limits),
+            None => block.safe_speed(&limits),
+        };
+        previous = Some(block);
+        blocks.push(Some(block));
+    }
+    // a dwell at the very end
+    let trailing: f32 = dwell + events
+        .filter_map(|event| match event.op {
+            Op::Dwell(seconds) => Some(seconds),
+            _ => None,
+        })
+        .sum::<f32>();
+
+    // backwards: every move has to be able to slow down to whatever the
+    // next one starts at
+    let planned: Vec<(usize, Block)> = blocks.iter()
+        .enumerate()
+        .filter_map(|(i, block)| block.map(|block| (i, block)))
+        .collect();
+    let mut entry = vec![0.0; planned.len()];
+    let mut exit = 0.0f32;
+    for (j, (_, block)) in planned.iter().enumerate().rev() {
+        let reachable = (exit * exit + 2.0 * block.acceleration * block.length).sqrt();
+        entry[j] = block.max_entry.min(reachable);
+        exit = entry[j];
+    }
+    // forwards: and speed up no faster than it can
+    for j in 0..planned.len().saturating_sub(1) {
+        let block = &planned[j].1;
+        let reachable = (entry[j] * entry[j] + 2.0 * block.acceleration * block.length).sqrt();
+        entry[j + 1] = entry[j + 1].min(reachable);
+    }
+
+    let mut moves = vec![0.0; toolpath.moves.len()];
+    for (j, (i, block)) in planned.iter().enumerate() {
+        let exit = entry.get(j + 1).copied().unwrap_or(0.0);
+        moves[*i] = block.dwell + block.time(entry[j], exit);
+    }
+    if let Some(last) = moves.last_mut() {
+        *last += trailing;
+    }
+
+    Estimate {
+        total: moves.iter().sum(),
+        moves,
+    }
+}
+
+// how the estimate lines up against what the slicer wrote in the file
+pub struct Comparison {
+    pub planner: f32,
+    // ; estimated printing time, or cura's ;TIME:
+    pub slicer: Option<f32>,
+    // the remaining time on the first M73, which is the slicers estimate
+    // as the firmware will show it
+    pub progress: Option<f32>,
+    // the worst disagreement between the time left by the planner and
+    // by M73 over the print, in seconds. positive if the planner is slower
+    pub drift: Option<f32>,
+}
+
+pub fn compare(toolpath: &Toolpath, estimate: &Estimate) -> Comparison {
+    let mut slicer = None;
+    let mut progress = None;
+    let mut drift: Option<f32> = None;
+    let mut elapsed = 0.0;
+    let mut counted = 0;
+    for event in toolpath.events.iter() {
+        match event.op {
+            Op::Estimate(seconds) => slicer = Some(seconds),
+            Op::Progress { remaining: Some(minutes), .. } => {
+                let remaining = minutes * 60.0;
+                progress.get_or_insert(remaining);
+                let before = event.before.min(estimate.moves.len());
+                elapsed += estimate.moves[counted..before].iter().sum::<f32>();
+                counted = before;
+                let difference = (estimate.total - elapsed) - remaining;
+                match drift {
+                    Some(drift) if drift.abs() >= difference.abs() => {},
+                    _ => drift = Some(difference),
+                }
+            },
+            _ => {},
+        }
+    }
+    Comparison {
+        planner: estimate.total,
+        slicer,
+        progress,
+        drift,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+
+    const X: [f32; 4] = [1.0, 0.0, 0.0, 0.0];
+    const Y: [f32; 4] = [0.0, 1.0, 0.0, 0.0];
+
+    fn block(unit: [f32; 4], length: f32, nominal: f32, acceleration: f32) -> Block {
+        Block {
+            length,
+            unit,
+            nominal,
+            acceleration,
+            accel_to_decel: acceleration,
+            max_entry: 0.0,
+            dwell: 0.0,
+        }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn trapezoids_and_triangles() {
+        // 0.1s and 5mm each to get to 100mm/s and back, 90mm at 100mm/s
+        assert!(close(block(X, 100.0, 100.0, 1000.0).time(0.0, 0.0), 1.1));
+        // starting at 10mm/s saves 0.01s and 0.05mm of speeding up
+        assert!(close(block(X, 100.0, 100.0, 1000.0).time(10.0, 0.0), 1.0905));
+        // too short to get to 200mm/s, it turns round at 100mm/s halfway
+        assert!(close(block(X, 10.0, 200.0, 1000.0).time(0.0, 0.0), 0.2));
+        // klipper tops out lower with a lower accel_to_decel, at the 50mm/s
+        // it would reach halfway at 500mm/s^2. it still speeds up and slows
+        // down at the full 1000, in 0.05s and 1.25mm each, and cruises the
+        // 2.5mm in between
+        let short = Block { accel_to_decel: 500.0, ..block(X, 5.0, 200.0, 1000.0) };
+        assert!(close(short.time(0.0, 0.0), 0.15));
+    }
+
+    #[test]
+    fn square_corners() {
+        let previous = block(X, 10.0, 100.0, 1000.0);
+        let next = block(Y, 10.0, 100.0, 1000.0);
+
+        // each axis goes from 0 to 100mm/s or back, so the jerk limit is
+        // the speed through the corner
+        let marlin = Limits::default();
+        assert!(close(junction_speed(&previous, &next, &marlin), 10.0));
+
+        // sin(45)/(1 - sin(45)) is 1 + sqrt(2)
+        let deviation = Limits { cornering: Cornering::JunctionDeviation(0.05), ..marlin };
+        let expected = (1000.0 * 0.05 * (1.0 + std::f32::consts::SQRT_2)).sqrt();
+        assert!(close(deviation_speed(&previous, &next, 0.05, 1000.0), expected));
+        assert!(close(junction_speed(&previous, &next, &deviation), expected));
+
+        // which is what square corner velocity is named for
+        assert!(close(junction_speed(&previous, &next, &Limits::klipper()), 5.0));
+    }
+
+    #[test]
+    fn straight_on_goes_as_fast_as_either_move() {
+        let previous = block(X, 10.0, 100.0, 1000.0);
+        let next = block(X, 10.0, 60.0, 1000.0);
+        assert_eq!(deviation_speed(&previous, &next, 0.05, 1000.0), f32::INFINITY);
+        let deviation = Limits { cornering: Cornering::JunctionDeviation(0.05), ..Limits::default() };
+        for limits in [Limits::default(), deviation, Limits::klipper()] {
+            assert_eq!(junction_speed(&previous, &next, &limits), 60.0);
+        }
+        // and a reversal comes to a stop
+        let back = block([-1.0, 0.0, 0.0, 0.0], 10.0, 100.0, 1000.0);
+        assert_eq!(junction_speed(&previous, &back, &Limits::klipper()), 0.0);
+    }
+
+    #[test]
+    fn estimate_against_m73() {
+        // no jerk, so both moves start and end stopped and take 1.1s each
+        // like the trapezoid above. M73 says 2.4s and then 1.2s are left
+        let toolpath = loader::load_bytes(b"M204 S1000\nM205 X0 Y0\nM73 P0 R0.04\nG1 X100 F6000\nM73 P50 R0.02\nG1 X100 Y100\n");
+        let estimate = estimate(&toolpath, Limits::for_toolpath(&toolpath));
+        assert_eq!(estimate.moves.len(), 2);
+        assert!(close(estimate.moves[0], 1.1) && close(estimate.moves[1], 1.1));
+        assert!(close(estimate.total, 2.2));
+
+        let comparison = compare(&toolpath, &estimate);
+        assert!(close(comparison.progress.unwrap(), 2.4));
+        // 0.2s short at the start and 0.1s halfway
+        assert!(close(comparison.drift.unwrap(), -0.2));
+    }
+
+    #[test]
+    fn klipper_files_get_klippers_defaults() {
+        let marlin = loader::load_bytes(b"G1 X10 F6000\n");
+        assert_eq!(Limits::for_toolpath(&marlin), Limits::default());
+        let commands = loader::load_bytes(b"PRINT_START\nG1 X10 F6000\n");
+        assert_eq!(Limits::for_toolpath(&commands), Limits::klipper());
+        let sliced = loader::load_bytes(b"G1 X10 F6000\n; gcode_flavor = klipper\n");
+        assert_eq!(Limits::for_toolpath(&sliced), Limits::klipper());
+    }
+}
@@ -0,0 +1,151 @@
+use std::time::Instant;
+
+use fig::toolpath::Move;
+
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 1024.0;
+
+// replays the print in (sped up) real time. the path is drawn up to
+// wherever the nozzle has got to, see Fig::visible_vertices
+pub struct Playback {
+    active: bool,
+    playing: bool,
+    // seconds into the print
+    time: f32,
+    // multiple of real time
+    speed: f32,
+    // when each move finishes, in seconds from the start
+    ends: Vec<f32>,
+    last_tick: Instant,
+}
+
+impl Playback {
+    // durations are per move, in seconds
+    pub fn new(durations: impl Iterator<Item = f32>) -> Self {
+        let mut playback = Self {
+            active: false,
+            playing: false,
+            time: 0.0,
+            speed: 16.0,
+            ends: Vec::new(),
+            last_tick: Instant::now(),
+        };
+        playback.set_durations(durations);
+        playback
+    }
+
+    // for a new or reloaded file. the time is kept where it was if its
+    // still inside the print
+    pub fn set_durations(&mut self, durations: impl Iterator<Item = f32>) {
+        let mut total = 0.0;
+        self.ends = durations.map(|d| {
+            total += d;
+            total
+        }).collect();
+        self.time = self.time.min(self.total());
+    }
+
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    pub fn playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn total(&self) -> f32 {
+        self.ends.last().copied().unwrap_or(0.0)
+    }
+
+    // switching it on starts from the beginning
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+        self.playing = self.active;
+        self.time = 0.0;
+        self.last_tick = Instant::now();
+    }
+
+    pub fn play_pause(&mut self) {
+        if !self.active {
+            return;
+        }
+        // playing again from the end starts over
+        if !self.playing && self.time >= self.total() {
+            self.time = 0.0;
+        }
+        self.playing = !self.playing;
+        self.last_tick = Instant::now();
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed * 2.0).min(MAX_SPEED);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = (self.speed * 0.5).max(MIN_SPEED);
+    }
+
+    // jumps to the end of the current move, or the end of the one before
+    // it, and pauses there
+    pub fn step(&mut self, forward: bool) {
+        if !self.active || self.ends.is_empty() {
+            return;
+        }
+        self.playing = false;
+        let current = self.current();
+        self.time = if forward {
+            // already sitting on the end of this one
+            let i = if self.time >= self.ends[current] { current + 1 } else { current };
+            self.ends[i.min(self.ends.len() - 1)]
+        } else {
+            match current.checked_sub(1) {
+                Some(i) if self.time <= self.ends[i] => i.checked_sub(1).map_or(0.0, |i| self.ends[i]),
+                Some(i) => self.ends[i],
+                None => 0.0,
+            }
+        };
+    }
+
+    // call once per frame
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        let elapsed = (now - self.last_tick).as_secs_f32();
+        self.last_tick = now;
+        if !self.active || !self.playing {
+            return;
+        }
+        self.time = (self.time + elapsed * self.speed).min(self.total());
+        if self.time >= self.total() {
+            self.playing = false;
+        }
+    }
+
+    // the move the nozzle is on
+    pub fn current(&self) -> usize {
+        self.ends
+            .partition_point(|end| *end <= self.time)
+            .min(self.ends.len().saturating_sub(1))
+    }
+
+    // where the nozzle is on the current move
+    pub fn nozzle(&self, moves: &[Move]) -> Option<[f32; 3]> {
+        let i = self.current();
+        let m = moves.get(i)?;
+        let start = if i == 0 { 0.0 } else { self.ends[i - 1] };
+        let length = self.ends[i] - start;
+        let t = if length > 0.0 { ((self.time - start) / length).clamp(0.0, 1.0) } else { 1.0 };
+        Some([
+            m.start[0] + (m.end[0] - m.start[0]) * t,
+            m.start[1] + (m.end[1] - m.start[1]) * t,
+            m.start[2] + (m.end[2] - m.start[2]) * t,
+        ])
+    }
+}
@@ -0,0 +1,238 @@
+use crate::toolpath::{Layer, Move, Op, Toolpath};
+
+// how far z has to come back down to count as the end of a hop rather
+// than the next layer, mm
+const HOP_TOLERANCE: f32 = 0.001;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Retract,
+    Unretract,
+    ZHop,
+    Wipe,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 4] = [Kind::Retract, Kind::Unretract, Kind::ZHop, Kind::Wipe];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Retract => "retract",
+            Kind::Unretract => "unretract",
+            Kind::ZHop => "z hop",
+            Kind::Wipe => "wipe",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Marker {
+    pub kind: Kind,
+    // where it happened. for a retraction thats where the printing stopped
+    pub position: [f32; 3],
+    // index into Toolpath::moves of the first move involved
+    pub index: usize,
+    pub line: u32,
+    // mm of filament for retractions, mm of z for hops and mm of xy for
+    // wipes
+    pub amount: f32,
+}
+
+// how many of each kind of marker, and how much they add up to
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Counts {
+    pub counts: [usize; 4],
+    pub amounts: [f32; 4],
+}
+
+impl Counts {
+    pub fn count(&self, kind: Kind) -> usize {
+        self.counts[kind.index()]
+    }
+
+    pub fn amount(&self, kind: Kind) -> f32 {
+        self.amounts[kind.index()]
+    }
+
+    pub fn average(&self, kind: Kind) -> f32 {
+        match self.count(kind) {
+            0 => 0.0,
+            count => self.amount(kind) / count as f32,
+        }
+    }
+
+    fn add(&mut self, marker: &Marker) {
+        self.counts[marker.kind.index()] += 1;
+        self.amounts[marker.kind.index()] += marker.amount;
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Analysis {
+    // in file order
+    pub markers: Vec<Marker>,
+    pub total: Counts,
+    // lines up with Toolpath::layers
+    pub layers: Vec<Counts>,
+}
+
+// finds every retraction, unretraction, z hop and wipe. a retraction spread
+// over several moves (like prusa and super slicer do while wiping) counts
+// once, at the first of them
+pub fn analyze(toolpath: &Toolpath, layers: &[Layer]) -> Analysis {
+    let mut markers = Vec::new();
+    let moves = &toolpath.moves;
+
+    let mut i = 0;
+    while i < moves.len() {
+        let m = &moves[i];
+        let kind = if m.e < 0.0 {
+            Kind::Retract
+        } else if m.e > 0.0 && !m.is_extrusion() {
+            Kind::Unretract
+        } else {
+            i += 1;
+            continue;
+        };
+        let same = |m: &Move| if kind == Kind::Retract { m.e < 0.0 } else { m.e > 0.0 && !m.is_extrusion() };
+        let run = moves[i..].iter().take_while(|m| same(m)).count();
+        let amount = moves[i..i + run].iter().map(|m| m.e.abs()).sum();
+        let position = if kind == Kind::Retract { m.start } else { moves[i + run - 1].end };
+        markers.push(Marker { kind, position, index: i, line: m.line, amount });
+        i += run;
+    }
+
+    // a hop goes up without printing then comes back down to where it
+    // started. going up and staying there is a layer change
+    let mut hop: Option<(usize, f32)> = None;
+    for (i, m) in moves.iter().enumerate() {
+        if m.is_extrusion() {
+            hop = None;
+        } else if m.end[2] > m.start[2] {
+            hop.get_or_insert((i, m.start[2]));
+        } else if m.end[2] < m.start[2] {
+            if let Some((start, z)) = hop.take() {
+                let top = moves[start..=i].iter().map(|m| m.end[2]).fold(z, f32::max);
+                if m.end[2] <= top - HOP_TOLERANCE {
+                    markers.push(Marker {
+                        kind: Kind::ZHop,
+                        position: moves[start].start,
+                        index: start,
+                        line: moves[start].line,
+                        amount: top - z,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut wipe_start = None;
+    for event in toolpath.events.iter() {
+        match event.op {
+            Op::Wipe { start: true } => wipe_start = Some(event.before),
+            Op::Wipe { start: false } => {
+                let Some(start) = wipe_start.take() else { continue };
+                let wiped = &moves[start.min(moves.len())..event.before.min(moves.len())];
+                let Some(first) = wiped.first() else { continue };
+                let length = wiped
+                    .iter()
+                    .map(|m| ((m.end[0] - m.start[0]).powi(2) + (m.end[1] - m.start[1]).powi(2)).sqrt())
+                    .sum();
+                markers.push(Marker {
+                    kind: Kind::Wipe,
+                    position: first.start,
+                    index: start,
+                    line: first.line,
+                    amount: length,
+                });
+            },
+            _ => {},
+        }
+    }
+
+    markers.sort_by_key(|marker| marker.index);
+    let mut analysis = Analysis {
+        layers: vec![Counts::default(); layers.len()],
+        ..Analysis::default()
+    };
+    for marker in markers.iter() {
+        analysis.total.add(marker);
+        let layer = layers.partition_point(|layer| layer.moves.end <= marker.index);
+        if let Some(counts) = analysis.layers.get_mut(layer) {
+            counts.add(marker);
+        }
+    }
+    analysis.markers = markers;
+    analysis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn retractions_over_several_moves_count_once() {
+        // prusa style, retracting while it wipes and then the rest in place
+        let text = "\
+M83
+G1 Z0.2 F3000
+G1 X10 Y10 E1
+;WIPE_START
+G1 X6 Y10 E-0.4
+G1 X4 Y10 E-0.2
+;WIPE_END
+G1 E-0.2
+G1 Z0.6
+G1 X30 Y30
+G1 Z0.2
+G1 E0.8
+G1 X40 Y30 E1
+";
+        let toolpath = loader::load_bytes(text.as_bytes());
+        let analysis = analyze(&toolpath, &toolpath.layers());
+
+        let markers: Vec<(Kind, usize, u32)> = analysis.markers.iter().map(|m| (m.kind, m.index, m.line)).collect();
+        assert_eq!(
+            markers,
+            vec![(Kind::Retract, 2, 5), (Kind::Wipe, 2, 5), (Kind::ZHop, 5, 9), (Kind::Unretract, 8, 12)],
+        );
+        let [retract, wipe, hop, unretract] = [0, 1, 2, 3].map(|i| analysis.markers[i]);
+        assert!(close(retract.amount, 0.8) && retract.position == [10.0, 10.0, 0.2], "{:?}", retract);
+        assert!(close(wipe.amount, 6.0), "{:?}", wipe);
+        assert!(close(hop.amount, 0.4) && hop.position == [4.0, 10.0, 0.2], "{:?}", hop);
+        assert!(close(unretract.amount, 0.8) && unretract.position == [30.0, 30.0, 0.2], "{:?}", unretract);
+        assert_eq!(analysis.total.counts, [1, 1, 1, 1]);
+        assert!(close(analysis.total.average(Kind::Retract), 0.8));
+    }
+
+    #[test]
+    fn going_up_and_staying_is_a_layer_change() {
+        let text = "M83\nG1 Z0.2 F3000\nG1 X10 Y10 E1\nG1 E-1\nG1 Z0.4\nG1 E1\nG1 X20 Y10 E1\nG1 Z0.8\nG1 X30 Y10\nG1 Z0.4\nG1 X40 Y10 E1\n";
+        let toolpath = loader::load_bytes(text.as_bytes());
+        let layers = toolpath.layers();
+        let analysis = analyze(&toolpath, &layers);
+
+        let markers: Vec<(Kind, usize)> = analysis.markers.iter().map(|m| (m.kind, m.index)).collect();
+        assert_eq!(markers, vec![(Kind::Retract, 2), (Kind::Unretract, 4), (Kind::ZHop, 6)]);
+        assert_eq!(layers.len(), 2);
+        // markers go on the layer of the move they start at
+        assert_eq!(analysis.layers[0].counts, [1, 0, 0, 0]);
+        assert_eq!(analysis.layers[1].counts, [0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn unfinished_hops_and_wipes_arent_counted() {
+        let text = "G1 Z0.2 F3000\nG1 X10 Y10 E1\n;WIPE_START\nG1 X20 Y10\nG1 Z0.6\nG1 X30 Y10 E1\n";
+        let toolpath = loader::load_bytes(text.as_bytes());
+        assert_eq!(analyze(&toolpath, &toolpath.layers()).markers, Vec::new());
+    }
+}
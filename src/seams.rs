@@ -0,0 +1,164 @@
+use std::ops::Range;
+
+use crate::toolpath::{Feature, Layer, Move, Toolpath};
+
+// ends closer than this are the same loop coming back round, mm
+const CLOSED: f32 = 1.0;
+
+// one perimeter loop. the seam is where it starts, and ends if its
+// closed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Seam {
+    pub start: [f32; 3],
+    pub end: [f32; 3],
+    // the outside wall, the one the seam shows on
+    pub external: bool,
+    // indices into Toolpath::moves
+    pub moves: Range<usize>,
+    pub line: u32,
+}
+
+impl Seam {
+    pub fn closed(&self) -> bool {
+        let [dx, dy, dz] = [0, 1, 2].map(|axis| self.end[axis] - self.start[axis]);
+        (dx * dx + dy * dy + dz * dz).sqrt() < CLOSED
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Seams {
+    // in file order
+    pub seams: Vec<Seam>,
+    // the seams on each layer, lines up with Toolpath::layers
+    pub layers: Vec<Range<usize>>,
+}
+
+impl Seams {
+    pub fn layer(&self, layer: usize) -> &[Seam] {
+        match self.layers.get(layer) {
+            Some(range) => &self.seams[range.clone()],
+            None => &[],
+        }
+    }
+}
+
+// which wall a move is printing, if any. overhangs are marked partway
+// round a loop so they count as part of it rather than starting their own
+fn wall(m: &Move) -> Option<bool> {
+    if !m.is_extrusion() {
+        return None;
+    }
+    match m.feature {
+        Feature::ExternalPerimeter | Feature::OverhangPerimeter => Some(true),
+        Feature::Perimeter => Some(false),
+        _ => None,
+    }
+}
+
+// splits the perimeters into loops wherever the nozzle stops printing (a
+// travel or a retraction) or switches between inside and outside walls
+pub fn find(toolpath: &Toolpath, layers: &[Layer]) -> Seams {
+    let moves = &toolpath.moves;
+    let mut seams = Vec::new();
+    let mut i = 0;
+    while i < moves.len() {
+        let Some(external) = wall(&moves[i]) else {
+            i += 1;
+            continue;
+        };
+        let count = moves[i..].iter().take_while(|m| wall(m) == Some(external)).count();
+        seams.push(Seam {
+            start: moves[i].start,
+            end: moves[i + count - 1].end,
+            external,
+            moves: i..i + count,
+            line: moves[i].line,
+        });
+        i += count;
+    }
+
+    let layers = layers
+        .iter()
+        .map(|layer| {
+            let first = seams.partition_point(|seam: &Seam| seam.moves.start < layer.moves.start);
+            let last = seams.partition_point(|seam: &Seam| seam.moves.start < layer.moves.end);
+            first..last
+        })
+        .collect();
+    Seams { seams, layers }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+
+    #[test]
+    fn loops_split_at_walls_travels_and_retractions() {
+        let text = "\
+M83
+G1 Z0.2 F3000
+G1 X10 Y10
+;TYPE:Perimeter
+G1 X20 Y10 E1
+G1 X20 Y20 E1
+G1 X10 Y20 E1
+G1 X10 Y10 E1
+;TYPE:External perimeter
+G1 X5 Y10 E1
+;TYPE:Overhang perimeter
+G1 X5 Y5 E1
+;TYPE:External perimeter
+G1 X12 Y5 E1
+G1 X30 Y30
+G1 X40 Y30 E1
+G1 E-0.5
+G1 E0.5
+G1 X40 Y40 E1
+";
+        let toolpath = loader::load_bytes(text.as_bytes());
+        let seams = find(&toolpath, &toolpath.layers());
+
+        let found: Vec<(bool, Range<usize>, u32, bool)> = seams
+            .seams
+            .iter()
+            .map(|seam| (seam.external, seam.moves.clone(), seam.line, seam.closed()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (false, 2..6, 5, true),
+                // overhangs partway round dont start a loop of their own
+                (true, 6..9, 10, false),
+                (true, 10..11, 16, false),
+                (true, 13..14, 19, false),
+            ],
+        );
+        assert_eq!(seams.seams[0].start, [10.0, 10.0, 0.2]);
+        assert_eq!(seams.seams[1].end, [12.0, 5.0, 0.2]);
+    }
+
+    #[test]
+    fn seams_by_layer() {
+        let text = "\
+M83
+G1 Z0.2 F3000
+;TYPE:External perimeter
+G1 X10 Y0 E1
+;TYPE:Solid infill
+G1 X10 Y10 E1
+G1 Z0.4
+;TYPE:Perimeter
+G1 X0 Y10 E1
+;TYPE:External perimeter
+G1 X0 Y0 E1
+";
+        let toolpath = loader::load_bytes(text.as_bytes());
+        let layers = toolpath.layers();
+        let seams = find(&toolpath, &layers);
+
+        assert_eq!(seams.layers, vec![0..1, 1..3]);
+        assert_eq!(seams.layer(1).iter().map(|seam| seam.external).collect::<Vec<_>>(), vec![false, true]);
+        assert_eq!(seams.layer(2), &[]);
+    }
+}
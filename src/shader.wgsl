@@ -30,3 +30,74 @@ fn vs_main(
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     return vec4<f32>(0.3, 0.2, 0.1, 1.0);
 }
+
+// the other extruders, for files with tool changes
+@fragment
+fn fs_tool1(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.1, 0.25, 0.45, 1.0);
+}
+
+@fragment
+fn fs_tool2(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.1, 0.35, 0.15, 1.0);
+}
+
+@fragment
+fn fs_tool3(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.4, 0.1, 0.35, 1.0);
+}
+
+// the segment selected in the source panel
+@fragment
+fn fs_highlight(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 1.0, 0.4, 1.0);
+}
+
+// moves that leave the build volume
+@fragment
+fn fs_outside(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.15, 0.1, 1.0);
+}
+
+// the edges of the build volume
+@fragment
+fn fs_volume(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.25, 0.25, 0.3, 1.0);
+}
+
+// retraction markers, see markers.rs
+@fragment
+fn fs_retract(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.3, 0.8, 1.0);
+}
+
+@fragment
+fn fs_unretract(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.3, 0.9, 1.0, 1.0);
+}
+
+@fragment
+fn fs_zhop(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.4, 1.0, 0.4, 1.0);
+}
+
+@fragment
+fn fs_wipe(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.6, 0.1, 1.0);
+}
+
+@fragment
+fn fs_seam_external(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+}
+
+@fragment
+fn fs_seam_internal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.6, 0.5, 1.0, 1.0);
+}
+
+// the second file in comparison mode, opposite the usual brown
+@fragment
+fn fs_compare(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.1, 0.4, 0.5, 1.0);
+}
@@ -0,0 +1,103 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io,
+    ops::Deref,
+    path::Path,
+};
+
+use memmap2::Mmap;
+
+// a mapped file, or text that didnt come from one
+enum Text {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for Text {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Text::Mapped(map) => map,
+            Text::Owned(text) => text,
+        }
+    }
+}
+
+// the raw text of a gcode file with the offset every line starts at, so
+// any part of it can be shown without splitting the whole thing up front.
+// loader::open lexes the same mapping, so the file is only read once
+pub struct Source {
+    text: Text,
+    starts: Vec<usize>,
+}
+
+impl Source {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // theres nothing to map in an empty file
+        if file.metadata()?.len() == 0 {
+            return Ok(Self::from_bytes(Vec::new()));
+        }
+        // # safety
+        //
+        // the map lives as long as the source, which the viewer holds onto
+        // until the next reload. a slicer rewriting the file in place
+        // changes what it reads underneath it, and truncating it faults on
+        // the next read of the lost part. the watcher reloads as soon as the
+        // file changes, and slicers write a new file and rename it over the
+        // old one, which leaves the old map alone
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self::new(Text::Mapped(map)))
+    }
+
+    pub fn from_bytes(text: Vec<u8>) -> Self {
+        Self::new(Text::Owned(text))
+    }
+
+    fn new(text: Text) -> Self {
+        let mut starts = vec![0];
+        starts.extend(text.iter()
+            .enumerate()
+            .filter(|(_, b)| **b == b'\n')
+            .map(|(i, _)| i + 1));
+        // same as the loader, a trailing newline doesnt start another line
+        if text.last() == Some(&b'\n') {
+            starts.pop();
+        }
+        Self { text, starts }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.text
+    }
+
+    // number of lines
+    pub fn len(&self) -> u32 {
+        if self.text.is_empty() {
+            0
+        } else {
+            self.starts.len() as u32
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    // 1 based like Move::line, without the line ending
+    pub fn line(&self, number: u32) -> Option<Cow<'_, str>> {
+        if number == 0 || number > self.len() {
+            return None;
+        }
+        let i = number as usize - 1;
+        let start = self.starts[i];
+        let end = self.starts.get(i + 1).map_or(self.text.len(), |next| next - 1);
+        let line = &self.text[start..end];
+        // the last line still has its newline on the end
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        Some(String::from_utf8_lossy(line))
+    }
+}
@@ -0,0 +1,243 @@
+use crate::config::Config;
+use crate::toolpath::{Feature, Layer, Toolpath};
+
+// what one extruder is loaded with
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Filament {
+    // mm
+    pub diameter: f32,
+    // g/cm^3
+    pub density: f32,
+    // per kg
+    pub cost: f32,
+}
+
+impl Default for Filament {
+    // pla, with no price
+    fn default() -> Self {
+        Self {
+            diameter: 1.75,
+            density: 1.24,
+            cost: 0.0,
+        }
+    }
+}
+
+impl Filament {
+    // one per extruder, going by the slicers config block. anything
+    // missing falls back to the default
+    pub fn from_config(config: &Config) -> Vec<Filament> {
+        let diameters = config.get_list("filament_diameter");
+        let densities = config.get_list("filament_density");
+        let costs = config.get_list("filament_cost");
+        let count = diameters.len().max(densities.len()).max(costs.len()).max(1);
+        let default = Filament::default();
+        (0..count)
+            .map(|i| Filament {
+                diameter: diameters.get(i).copied().unwrap_or(default.diameter),
+                density: densities.get(i).copied().unwrap_or(default.density),
+                cost: costs.get(i).copied().unwrap_or(default.cost),
+            })
+            .collect()
+    }
+
+    // mm^2
+    pub fn area(&self) -> f32 {
+        std::f32::consts::PI * self.diameter * self.diameter * 0.25
+    }
+
+    // what a length of this filament comes to
+    pub fn usage(&self, length: f32) -> Usage {
+        let volume = length * self.area();
+        let weight = volume * 0.001 * self.density;
+        Usage {
+            length,
+            volume,
+            weight,
+            cost: weight * 0.001 * self.cost,
+        }
+    }
+}
+
+// anything set here wins over the config block, for files that dont have
+// one or when the spool on the printer isnt what the slicer was told
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Overrides {
+    pub diameter: Option<f32>,
+    pub density: Option<f32>,
+    pub cost: Option<f32>,
+}
+
+impl Overrides {
+    // the same for every extruder
+    pub fn apply(&self, filaments: &mut [Filament]) {
+        for filament in filaments {
+            filament.diameter = self.diameter.unwrap_or(filament.diameter);
+            filament.density = self.density.unwrap_or(filament.density);
+            filament.cost = self.cost.unwrap_or(filament.cost);
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Usage {
+    // mm of filament
+    pub length: f32,
+    // mm^3
+    pub volume: f32,
+    // g
+    pub weight: f32,
+    pub cost: f32,
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.length += other.length;
+        self.volume += other.volume;
+        self.weight += other.weight;
+        self.cost += other.cost;
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub total: Usage,
+    // lines up with Toolpath::layers
+    pub layers: Vec<Usage>,
+    // in the order each feature first shows up
+    pub features: Vec<(Feature, Usage)>,
+    // indexed by extruder
+    pub extruders: Vec<Usage>,
+}
+
+impl Stats {
+    pub fn feature(&self, feature: Feature) -> Option<Usage> {
+        self.features.iter().find(|(f, _)| *f == feature).map(|(_, usage)| *usage)
+    }
+}
+
+// adds up the filament pushed through the nozzle. only extrusion counts,
+// retractions and the unretracts after them cancel out and would otherwise
+// get charged to whichever feature they happened to land in
+pub fn filament(toolpath: &Toolpath, layers: &[Layer], filaments: &[Filament]) -> Stats {
+    let mut stats = Stats {
+        layers: vec![Usage::default(); layers.len()],
+        ..Stats::default()
+    };
+    let default = Filament::default();
+    let mut layer = 0;
+    for (i, m) in toolpath.moves.iter().enumerate() {
+        if !m.is_extrusion() {
+            continue;
+        }
+        let extruder = m.tool;
+        let usage = filaments.get(extruder).unwrap_or(&default).usage(m.e);
+
+        stats.total += usage;
+        while layer + 1 < layers.len() && layers[layer].moves.end <= i {
+            layer += 1;
+        }
+        if let Some(total) = stats.layers.get_mut(layer) {
+            *total += usage;
+        }
+        match stats.features.iter_mut().find(|(feature, _)| *feature == m.feature) {
+            Some((_, total)) => *total += usage,
+            None => stats.features.push((m.feature, usage)),
+        }
+        if stats.extruders.len() <= extruder {
+            stats.extruders.resize(extruder + 1, Usage::default());
+        }
+        stats.extruders[extruder] += usage;
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4 * b.abs().max(1.0)
+    }
+
+    fn filaments(text: &str) -> Vec<Filament> {
+        Filament::from_config(&loader::load_bytes(text.as_bytes()).config)
+    }
+
+    #[test]
+    fn length_to_volume_to_weight_to_cost() {
+        let pla = Filament { diameter: 1.75, density: 1.24, cost: 25.0 };
+        // pi * 1.75^2 / 4
+        assert!(close(pla.area(), 2.405282));
+        let usage = pla.usage(1000.0);
+        assert_eq!(usage.length, 1000.0);
+        // a metre of it is 2.4cm^3, 2.98g and 7.5 cents at 25 a kilo
+        assert!(close(usage.volume, 2405.282));
+        assert!(close(usage.weight, 2.98255));
+        assert!(close(usage.cost, 0.07456375));
+    }
+
+    #[test]
+    fn filaments_from_prusa_and_orca_settings() {
+        // one value per extruder, and extruders short a value get the
+        // default for it
+        let prusa = filaments("G28\n; filament_cost = 25\n; filament_density = 1.24,1.27\n; filament_diameter = 1.75,2.85\n");
+        assert_eq!(prusa, [
+            Filament { diameter: 1.75, density: 1.24, cost: 25.0 },
+            Filament { diameter: 2.85, density: 1.27, cost: 0.0 },
+        ]);
+        let orca = filaments("G28\n; filament_diameter = 1.75\n; filament_density = 1.26\n; filament_cost = 20.99\n");
+        assert_eq!(orca, [Filament { diameter: 1.75, density: 1.26, cost: 20.99 }]);
+    }
+
+    #[test]
+    fn cura_files_fall_back_to_the_default() {
+        // cura only says how much it used, not what with
+        let cura = filaments(";FLAVOR:Marlin\n;TIME:1234\n;Filament used: 1.23456m\n;Layer height: 0.2\nG28\n");
+        assert_eq!(cura, [Filament::default()]);
+    }
+
+    #[test]
+    fn overrides_win_over_the_config() {
+        let mut filaments = filaments("; filament_diameter = 1.75,1.75\n; filament_density = 1.24,1.27\n; filament_cost = 25,30\n");
+        let overrides = Overrides { diameter: Some(2.85), density: None, cost: Some(40.0) };
+        overrides.apply(&mut filaments);
+        assert_eq!(filaments, [
+            Filament { diameter: 2.85, density: 1.24, cost: 40.0 },
+            Filament { diameter: 2.85, density: 1.27, cost: 40.0 },
+        ]);
+    }
+
+    #[test]
+    fn filament_by_layer_feature_and_extruder() {
+        let text = "\
+M83
+G1 Z0.2 F3000
+;TYPE:External perimeter
+G1 X10 Y0 E1
+;TYPE:Internal infill
+G1 X20 Y0 E2
+G1 E-0.5
+G1 E0.5
+G1 Z0.4
+T1
+G1 X30 Y0 E4
+";
+        let toolpath = loader::load_bytes(text.as_bytes());
+        let layers = toolpath.layers();
+        let filaments = [Filament::default(), Filament { density: 2.48, ..Filament::default() }];
+        let stats = filament(&toolpath, &layers, &filaments);
+
+        // the retraction and the unretract after it dont count
+        assert_eq!(stats.total.length, 7.0);
+        assert_eq!(stats.layers.iter().map(|usage| usage.length).collect::<Vec<_>>(), [3.0, 4.0]);
+        assert_eq!(stats.feature(Feature::ExternalPerimeter).map(|usage| usage.length), Some(1.0));
+        assert_eq!(stats.feature(Feature::InternalInfill).map(|usage| usage.length), Some(6.0));
+        assert_eq!(stats.extruders.iter().map(|usage| usage.length).collect::<Vec<_>>(), [3.0, 4.0]);
+        // twice as dense, twice the weight per mm
+        let per_mm = filaments[0].usage(1.0).weight;
+        assert!(close(stats.extruders[1].weight, 8.0 * per_mm));
+        assert!(close(stats.total.weight, 11.0 * per_mm));
+    }
+}
@@ -1,155 +1,701 @@
-pub struct TextLine {
-    buffer: Buffer,
-    metadata: Metadata,
+use std::collections::HashMap;
+
+use bytemuck::Zeroable;
+
+use crate::font::{Backend, Bitmap};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
 }
 
-impl TextLine {
-    pub fn empty() -> Self {
-        Self {
-            buffer: Buffer::from(""),
-            metadata: Metadata::empty(),
-        }
-    }
+// where a glyph ended up in the atlas and how to place it relative to the
+// pen position (see font::Bitmap)
+#[derive(Copy, Clone, Debug)]
+pub struct CachedGlyph {
+    pub rect: Rect,
+    pub left: f32,
+    pub top: f32,
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Metadata {
-    pub pixel_size: [u32; 2],
-    pub pixel_position: [u32; 2],
-    length: u32,
-    pub kerning: u32,
+// glyphs are rasterized the first time theyre asked for at a given size
+// and packed into rows ("shelves") of a single channel texture. nothing is
+// ever evicted, at the sizes we draw text at this fills up very slowly
+pub struct Atlas {
+    pub width: u32,
+    pub height: u32,
+    font: Box<dyn Backend>,
+    pixels: Vec<u8>,
+    glyphs: HashMap<(char, u32), Option<CachedGlyph>>,
+    shelf: Rect,
+    dirty: bool,
 }
 
-impl Metadata {
-    pub fn empty() -> Self {
+impl Atlas {
+    pub fn new(font: Box<dyn Backend>, width: u32, height: u32) -> Self {
         Self {
-            pixel_size: [0; 2],
-            pixel_position: [0; 2],
-            length: 0,
-            kerning: 0,
+            width,
+            height,
+            font,
+            pixels: vec![0; (width * height) as usize],
+            glyphs: HashMap::new(),
+            shelf: Rect { x: 0, y: 0, width: 0, height: 0 },
+            dirty: true,
         }
     }
-    pub fn from(buffer: &Buffer) -> Self {
-        Self {
-            pixel_size: [0; 2],
-            pixel_position: [0; 2],
-            length: buffer.chars.len() as u32,
-            kerning: 0,
+
+    pub fn font(&self) -> &dyn Backend {
+        self.font.as_ref()
+    }
+
+    pub fn glyph(&mut self, c: char, size: u32) -> Option<CachedGlyph> {
+        if let Some(glyph) = self.glyphs.get(&(c, size)) {
+            return *glyph;
+        }
+        let glyph = self.font
+            .rasterize(c, size as f32)
+            .and_then(|bitmap| self.insert(&bitmap));
+        self.glyphs.insert((c, size), glyph);
+        glyph
+    }
+
+    fn insert(&mut self, bitmap: &Bitmap) -> Option<CachedGlyph> {
+        let rect = self.allocate(bitmap.width, bitmap.height)?;
+        for row in 0..rect.height {
+            let src = (row * bitmap.width) as usize;
+            let dst = ((rect.y + row) * self.width + rect.x) as usize;
+            self.pixels[dst..dst + rect.width as usize]
+                .copy_from_slice(&bitmap.pixels[src..src + bitmap.width as usize]);
         }
+        self.dirty = true;
+        Some(CachedGlyph { rect, left: bitmap.left, top: bitmap.top })
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<Rect> {
+        // leave a pixel between glyphs so they dont bleed into each other
+        const PADDING: u32 = 1;
+        if self.shelf.x + width > self.width {
+            self.shelf = Rect {
+                x: 0,
+                y: self.shelf.y + self.shelf.height + PADDING,
+                width: 0,
+                height: 0,
+            };
+        }
+        if self.shelf.y + height > self.height || width > self.width {
+            eprintln!("glyph atlas is full");
+            return None;
+        }
+        let rect = Rect { x: self.shelf.x, y: self.shelf.y, width, height };
+        self.shelf.x += width + PADDING;
+        self.shelf.height = self.shelf.height.max(height);
+        Some(rect)
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    // true if glyphs were added since the last time this was called
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.dirty, false)
     }
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Glyph {
-    atlas_offset: [u32; 4],
+    // x, y, width, height in atlas pixels
+    atlas_rect: [u32; 4],
+    // x, y, width, height of the quad in pixels from the label origin,
+    // y going up
+    quad: [f32; 4],
     color: [f32; 4],
+    // index into the label metadata
+    label: u32,
+    // 1 for backgrounds, which fill their quad instead of sampling the atlas
+    solid: u32,
+    _padding: [u32; 2],
 }
 
 impl Glyph {
-    pub fn from(c: char) -> Self {
-        // this SUCKS but im *just* intoxicated enough to
-        // think its permissable
-        let offset = match c {
-            '0' => [0, 0],
-            '1' => [1, 0],
-            '2' => [2, 0],
-            '3' => [3, 0],
-            '4' => [4, 0],
-            '5' => [5, 0],
-            '6' => [6, 0],
-            '7' => [7, 0],
-            '8' => [8, 0],
-            '9' => [9, 0],
-
-            'A' => [0, 1],
-            'B' => [1, 1],
-            'C' => [2, 1],
-            'D' => [3, 1],
-            'E' => [4, 1],
-            'F' => [5, 1],
-            'G' => [6, 1],
-            'H' => [7, 1],
-            'I' => [8, 1],
-            'J' => [9, 1],
-            'K' => [10, 1],
-            'L' => [11, 1],
-            'M' => [12, 1],
-            'N' => [13, 1],
-            'O' => [14, 1],
-            'P' => [15, 1],
-            'Q' => [16, 1],
-            'R' => [17, 1],
-            'S' => [18, 1],
-            'T' => [19, 1],
-            'U' => [20, 1],
-            'V' => [21, 1],
-            'W' => [22, 1],
-            'X' => [23, 1],
-            'Y' => [24, 1],
-            'Z' => [25, 1],
-
-            'a' => [0, 2],
-            'b' => [1, 2],
-            'c' => [2, 2],
-            'd' => [3, 2],
-            'e' => [4, 2],
-            'f' => [5, 2],
-            'g' => [6, 2],
-            'h' => [7, 2],
-            'i' => [8, 2],
-            'j' => [9, 2],
-            'k' => [10, 2],
-            'l' => [11, 2],
-            'm' => [12, 2],
-            'n' => [13, 2],
-            'o' => [14, 2],
-            'p' => [15, 2],
-            'q' => [16, 2],
-            'r' => [17, 2],
-            's' => [18, 2],
-            't' => [19, 2],
-            'u' => [20, 2],
-            'v' => [21, 2],
-            'w' => [22, 2],
-            'x' => [23, 2],
-            'y' => [24, 2],
-            'z' => [25, 2],
-
-            _ => [0,0],
-        };
+    fn solid(quad: [f32; 4], color: [f32; 4]) -> Self {
         Self {
-            atlas_offset: [offset[0], offset[1], 0, 0],
-            color: match c {
-                ' ' => [0.0, 0.0, 0.0, 0.0],
-                _ => [1.0, 0.0, 0.0, 0.0],
-            },
+            atlas_rect: [0; 4],
+            quad,
+            color,
+            label: 0,
+            solid: 1,
+            _padding: [0; 2],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Style {
+    pub color: [f32; 4],
+    // drawn twice a pixel apart, theres only the one font loaded
+    pub bold: bool,
+    // filled in behind the run, from the ascender to the descender
+    pub background: Option<[f32; 4]>,
+}
+
+impl Style {
+    pub fn color(color: [f32; 4]) -> Self {
+        Self { color, bold: false, background: None }
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self::color([1.0, 1.0, 1.0, 1.0])
+    }
+}
+
+// a piece of text that all looks the same
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+impl Span {
+    pub fn new(text: &str, style: Style) -> Self {
+        Self { text: String::from(text), style }
+    }
+}
+
+// tab stops are this many spaces apart
+const TAB_WIDTH: f32 = 4.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Layout {
+    // lines line up against each other inside the widest one
+    pub align: Align,
+    // wrap at whitespace to keep lines narrower than this many pixels.
+    // words that dont fit on a line by themselves get broken anywhere
+    pub max_width: Option<f32>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self { align: Align::Left, max_width: None }
+    }
+}
+
+// a character and where it goes on its line
+#[derive(Copy, Clone, Debug)]
+struct Placed {
+    c: char,
+    style: Style,
+    x: f32,
+    advance: f32,
+}
+
+#[derive(Default)]
+struct Line {
+    placed: Vec<Placed>,
+    pen: f32,
+    // up to the end of the last thing that shows up
+    width: f32,
+}
+
+impl Line {
+    // (kerning, advance) for c if it went on the end of the line next
+    fn metrics(&self, font: &dyn Backend, pen: f32, previous: Option<char>, c: char, style: Style, size: f32) -> (f32, f32) {
+        if c == '\t' {
+            let stop = (font.advance(' ', size) * TAB_WIDTH).max(1.0);
+            return (0.0, ((pen / stop).floor() + 1.0) * stop - pen);
+        }
+        let kern = previous.map_or(0.0, |previous| font.kern(previous, c, size));
+        let bold = if style.bold { 1.0 } else { 0.0 };
+        (kern, font.advance(c, size) + bold)
+    }
+
+    fn place(&mut self, font: &dyn Backend, c: char, style: Style, size: f32) {
+        let previous = self.placed.last().map(|p| p.c);
+        let (kern, advance) = self.metrics(font, self.pen, previous, c, style, size);
+        let x = self.pen + kern;
+        self.placed.push(Placed { c, style, x, advance });
+        self.pen = x + advance;
+        // whitespace with a background is just as visible as anything else
+        if !c.is_whitespace() || style.background.is_some() {
+            self.width = self.pen;
+        }
+    }
+
+    // how wide the line would be with these on the end
+    fn measure(&self, font: &dyn Backend, chars: &[(char, Style)], size: f32) -> f32 {
+        let mut pen = self.pen;
+        let mut previous = self.placed.last().map(|p| p.c);
+        for &(c, style) in chars {
+            let (kern, advance) = self.metrics(font, pen, previous, c, style, size);
+            pen += kern + advance;
+            previous = Some(c);
         }
+        pen
     }
 }
 
+// splits spans into lines at newlines and, if theres a max width, at
+// whichever whitespace keeps them under it
+fn break_lines(spans: &[Span], size: f32, layout: &Layout, font: &dyn Backend) -> Vec<Line> {
+    let chars: Vec<(char, Style)> = spans.iter()
+        .flat_map(|span| span.text.chars().map(move |c| (c, span.style)))
+        .filter(|(c, _)| *c != '\r')
+        .collect();
+    let too_wide = |line: &Line, chars: &[(char, Style)]| match layout.max_width {
+        Some(max) => !line.placed.is_empty() && line.measure(font, chars, size) > max,
+        None => false,
+    };
+
+    let mut lines = Vec::new();
+    for mut rest in chars.split(|(c, _)| *c == '\n') {
+        let mut line = Line::default();
+        while !rest.is_empty() {
+            // the next word and whatever whitespace follows it. leading
+            // whitespace comes through as an empty word so indents stay
+            let word = rest.iter()
+                .position(|(c, _)| c.is_whitespace())
+                .unwrap_or(rest.len());
+            let end = rest[word..].iter()
+                .position(|(c, _)| !c.is_whitespace())
+                .map_or(rest.len(), |i| word + i);
+            if too_wide(&line, &rest[..word]) {
+                lines.push(std::mem::take(&mut line));
+            }
+            for &(c, style) in &rest[..word] {
+                if too_wide(&line, &[(c, style)]) {
+                    lines.push(std::mem::take(&mut line));
+                }
+                line.place(font, c, style, size);
+            }
+            for &(c, style) in &rest[word..end] {
+                line.place(font, c, style, size);
+            }
+            rest = &rest[end..];
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+// laid out glyphs, relative to the start of the first baseline
 pub struct Buffer {
-    chars: String,
     glyphs: Vec<Glyph>,
+    // of the widest line
+    pub width: f32,
+    // from the top of the first line to the bottom of the last ones
+    // descenders
+    pub height: f32,
+    pub ascent: f32,
 }
 
 impl Buffer {
-    pub fn from(text: &str) -> Self {
-        let glyphs: Vec<Glyph> = text
-            .chars()
-            .into_iter()
-            .map(|c| Glyph::from(c))
-            .collect();
+    // any number of lines at the given pixel size, going down from the
+    // first baseline. every span brings its own style, and kerning carries
+    // across span boundaries so splitting a word up doesnt move anything
+    pub fn paragraph(spans: &[Span], size: u32, layout: &Layout, atlas: &mut Atlas) -> Self {
+        let lines = break_lines(spans, size as f32, layout, atlas.font());
+        let ascent = atlas.font().ascent(size as f32);
+        let descent = size as f32 - ascent;
+        let line_height = atlas.font().line_height(size as f32).round();
+        let width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+
+        // backgrounds go first so everything after draws over them
+        let mut backgrounds = Vec::new();
+        let mut glyphs = Vec::new();
+        for (n, line) in lines.iter().enumerate() {
+            let left = match layout.align {
+                Align::Left => 0.0,
+                Align::Center => ((width - line.width) * 0.5).round(),
+                Align::Right => (width - line.width).round(),
+            };
+            let baseline = -(n as f32) * line_height;
+
+            // neighbouring characters with the same background share a quad
+            let fill = |start: f32, end: f32| {
+                [start.round(), baseline - descent.round(), (end - start).round(), size as f32]
+            };
+            let mut run: Option<([f32; 4], f32, f32)> = None;
+            for p in line.placed.iter() {
+                let x = left + p.x;
+                run = match (run, p.style.background) {
+                    (Some((color, start, _)), Some(background)) if color == background => {
+                        Some((color, start, x + p.advance))
+                    },
+                    (run, background) => {
+                        if let Some((color, start, end)) = run {
+                            backgrounds.push(Glyph::solid(fill(start, end), color));
+                        }
+                        background.map(|color| (color, x, x + p.advance))
+                    },
+                };
+
+                // spaces and tabs dont rasterize, and tabs would come back
+                // as the missing glyph box
+                if p.c.is_whitespace() {
+                    continue;
+                }
+                if let Some(glyph) = atlas.glyph(p.c, size) {
+                    let rect = glyph.rect;
+                    let glyph = Glyph {
+                        atlas_rect: [rect.x, rect.y, rect.width, rect.height],
+                        quad: [
+                            (x + glyph.left).round(),
+                            baseline - (glyph.top + rect.height as f32).round(),
+                            rect.width as f32,
+                            rect.height as f32,
+                        ],
+                        color: p.style.color,
+                        label: 0,
+                        solid: 0,
+                        _padding: [0; 2],
+                    };
+                    glyphs.push(glyph);
+                    if p.style.bold {
+                        let mut quad = glyph.quad;
+                        quad[0] += 1.0;
+                        glyphs.push(Glyph { quad, ..glyph });
+                    }
+                }
+            }
+            if let Some((color, start, end)) = run {
+                backgrounds.push(Glyph::solid(fill(start, end), color));
+            }
+        }
+        backgrounds.append(&mut glyphs);
         Self {
-            chars: String::from(text),
-            glyphs: glyphs,
+            glyphs: backgrounds,
+            width,
+            height: ascent + descent + (lines.len() - 1) as f32 * line_height,
+            ascent,
         }
     }
+}
+
+// which point of the label its position refers to. for labels placed on
+// the screen its also the corner (or middle) of the window the position is
+// measured from, so a TopRight label at [10, 10] sits 10px in from the top
+// right of the window whatever size it is
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+impl Anchor {
+    // where this anchor is in a box of the given size, y going up
+    fn point(&self, size: [f32; 2]) -> [f32; 2] {
+        match self {
+            Anchor::TopLeft => [0.0, size[1]],
+            Anchor::TopRight => [size[0], size[1]],
+            Anchor::BottomLeft => [0.0, 0.0],
+            Anchor::BottomRight => [size[0], 0.0],
+            Anchor::Center => [size[0] * 0.5, size[1] * 0.5],
+        }
+    }
+
+    // turns an offset in from this anchors part of the window into pixels
+    // from the bottom left
+    fn on_screen(&self, offset: [f32; 2], screen: [f32; 2]) -> [f32; 2] {
+        let [x, y] = self.point(screen);
+        match self {
+            Anchor::TopLeft => [x + offset[0], y - offset[1]],
+            Anchor::TopRight => [x - offset[0], y - offset[1]],
+            Anchor::BottomLeft => [x + offset[0], y + offset[1]],
+            Anchor::BottomRight => [x - offset[0], y + offset[1]],
+            Anchor::Center => [x + offset[0], y - offset[1]],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Position {
+    // logical pixels in from the anchored part of the window, y going down
+    Screen([f32; 2]),
+    // a point in the scene, projected through the camera every frame
+    World([f32; 3]),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    pub spans: Vec<Span>,
+    pub position: Position,
+    pub anchor: Anchor,
+    pub layout: Layout,
+    // logical pixels, like the screen position and max width. the layer
+    // scales all of them up to the windows actual pixels
+    pub size: u32,
+    pub visible: bool,
+}
+
+impl Label {
+    // plain white text
+    pub fn new(text: &str, position: Position) -> Self {
+        Self::styled(vec![Span::new(text, Style::default())], position)
+    }
+
+    pub fn styled(spans: Vec<Span>, position: Position) -> Self {
+        Self {
+            spans,
+            position,
+            anchor: Anchor::TopLeft,
+            layout: Layout::default(),
+            size: 16,
+            visible: true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LabelId(usize);
+
+// per label data for the shader, rewritten every frame so labels can move
+// around without having to lay out their glyphs again
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LabelData {
+    // pixels from the bottom left of the window to the start of the baseline
+    origin: [f32; 2],
+    visible: u32,
+    _padding: u32,
+}
+
+struct Slot {
+    label: Label,
+    // laid out size, kept around for anchoring
+    size: [f32; 2],
+    ascent: f32,
+}
+
+// all the labels on screen, drawn together in one instanced draw call.
+// glyphs are only rebuilt when a labels text or style changes
+pub struct Layer {
+    slots: Vec<Option<Slot>>,
+    glyphs: Vec<Glyph>,
+    data: Vec<LabelData>,
+    // physical pixels per logical pixel
+    scale: f32,
+    dirty: bool,
+}
+
+impl Layer {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            glyphs: Vec::new(),
+            data: Vec::new(),
+            scale: 1.0,
+            dirty: false,
+        }
+    }
+
+    // the windows scale factor. everything gets laid out again at the new
+    // size rather than stretched, so text stays sharp
+    pub fn set_scale(&mut self, scale: f32) {
+        if scale != self.scale {
+            self.scale = scale;
+            self.dirty = true;
+        }
+    }
+
+    pub fn add(&mut self, label: Label) -> LabelId {
+        self.dirty = true;
+        let slot = Some(Slot { label, size: [0.0; 2], ascent: 0.0 });
+        match self.slots.iter().position(|s| s.is_none()) {
+            Some(i) => {
+                self.slots[i] = slot;
+                LabelId(i)
+            },
+            None => {
+                self.slots.push(slot);
+                LabelId(self.slots.len() - 1)
+            },
+        }
+    }
+
+    // the slot gets reused by the next label added, so the id shouldnt be
+    // used again after this
+    pub fn remove(&mut self, id: LabelId) {
+        if let Some(slot) = self.slots.get_mut(id.0) {
+            *slot = None;
+            self.dirty = true;
+        }
+    }
+
+    // laid out size in logical pixels as of the last layout, for stacking
+    // labels in the same corner. hidden ones take up no room
+    pub fn size(&self, id: LabelId) -> [f32; 2] {
+        match self.slots.get(id.0) {
+            Some(Some(slot)) if slot.label.visible => [slot.size[0] / self.scale, slot.size[1] / self.scale],
+            _ => [0.0; 2],
+        }
+    }
+
+    // assumes the label is about to change, so its glyphs get rebuilt
+    pub fn get_mut(&mut self, id: LabelId) -> Option<&mut Label> {
+        self.dirty = true;
+        self.slots.get_mut(id.0)?.as_mut().map(|s| &mut s.label)
+    }
+
+    // cheaper than get_mut for labels that get refreshed every frame but
+    // dont often actually change
+    pub fn set_spans(&mut self, id: LabelId, spans: &[Span]) {
+        if let Some(Some(slot)) = self.slots.get_mut(id.0) {
+            if slot.label.spans != spans {
+                slot.label.spans = spans.to_vec();
+                self.dirty = true;
+            }
+        }
+    }
+
+    // moving a label doesnt need its glyphs rebuilt
+    pub fn set_position(&mut self, id: LabelId, position: Position) {
+        if let Some(Some(slot)) = self.slots.get_mut(id.0) {
+            slot.label.position = position;
+        }
+    }
+
+    // rebuilds glyphs if anything changed and works out where every label
+    // is this frame. screen is in physical pixels, view_proj is the cameras.
+    // world positions land in the viewport, the part of the screen the
+    // scene is drawn in, which shares its bottom left corner
+    pub fn layout(&mut self, atlas: &mut Atlas, screen: [u32; 2], view_proj: cgmath::Matrix4<f32>, viewport: [u32; 2]) {
+        let scale = self.scale;
+        if self.dirty {
+            self.dirty = false;
+            self.glyphs.clear();
+            for (i, slot) in self.slots.iter_mut().enumerate() {
+                let Some(slot) = slot else { continue };
+                let label = &slot.label;
+                let size = (label.size as f32 * scale).round() as u32;
+                let layout = Layout {
+                    max_width: label.layout.max_width.map(|width| width * scale),
+                    ..label.layout
+                };
+                let buffer = Buffer::paragraph(&label.spans, size, &layout, atlas);
+                slot.size = [buffer.width, buffer.height];
+                slot.ascent = buffer.ascent;
+                self.glyphs.extend(buffer.glyphs.into_iter().map(|g| Glyph { label: i as u32, ..g }));
+            }
+        }
+
+        let screen = [screen[0] as f32, screen[1] as f32];
+        let viewport = [viewport[0] as f32, viewport[1] as f32];
+        self.data.clear();
+        self.data.extend(self.slots.iter().map(|slot| {
+            let Some(Slot { label, size, ascent }) = slot else {
+                return LabelData::zeroed();
+            };
+            let point = match label.position {
+                Position::Screen([x, y]) => Some(label.anchor.on_screen([x * scale, y * scale], screen)),
+                Position::World(point) => project(view_proj, point, viewport),
+            };
+            match point {
+                Some(point) if label.visible => {
+                    // the box goes from the bottom of the last line up,
+                    // the origin is on the first lines baseline
+                    let anchor = label.anchor.point(*size);
+                    let baseline = size[1] - ascent;
+                    LabelData {
+                        // snap to whole pixels so the atlas isnt resampled
+                        origin: [(point[0] - anchor[0]).round(), (point[1] - anchor[1] + baseline).round()],
+                        visible: 1,
+                        _padding: 0,
+                    }
+                },
+                _ => LabelData::zeroed(),
+            }
+        }));
+    }
+
+    pub fn glyph_count(&self) -> usize {
+        self.glyphs.len()
+    }
+
     pub fn pack_glyphs(&self) -> &[u8] {
         bytemuck::cast_slice(self.glyphs.as_slice())
     }
-    pub fn len(&self) -> usize {
-        self.chars.len()
+
+    pub fn pack_labels(&self) -> &[u8] {
+        bytemuck::cast_slice(self.data.as_slice())
+    }
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// world space to pixels from the bottom left, or None if its behind the
+// camera
+pub fn project(view_proj: cgmath::Matrix4<f32>, point: [f32; 3], screen: [f32; 2]) -> Option<[f32; 2]> {
+    let clip = view_proj * cgmath::Vector4::new(point[0], point[1], point[2], 1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    Some([
+        (clip.x / clip.w + 1.0) * 0.5 * screen[0],
+        (clip.y / clip.w + 1.0) * 0.5 * screen[1],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every character a pixel square and ten pixels wide, so where things
+    // land can be worked out by hand
+    struct Mono;
+
+    impl Backend for Mono {
+        fn advance(&self, _: char, _: f32) -> f32 {
+            10.0
+        }
+
+        fn kern(&self, _: char, _: char, _: f32) -> f32 {
+            0.0
+        }
+
+        fn ascent(&self, size: f32) -> f32 {
+            size * 0.75
+        }
+
+        fn rasterize(&self, c: char, _: f32) -> Option<Bitmap> {
+            (!c.is_whitespace()).then(|| Bitmap { width: 1, height: 1, left: 0.0, top: -1.0, pixels: vec![255] })
+        }
+    }
+
+    // where each glyph starts, line by line
+    fn lefts(text: &str, align: Align) -> Vec<f32> {
+        let mut atlas = Atlas::new(Box::new(Mono), 64, 64);
+        let spans = [Span::new(text, Style::default())];
+        let buffer = Buffer::paragraph(&spans, 16, &Layout { align, max_width: None }, &mut atlas);
+        assert_eq!(buffer.width, 40.0);
+        buffer.glyphs.iter().map(|glyph| glyph.quad[0]).collect()
+    }
+
+    #[test]
+    fn lines_line_up_inside_the_widest_one() {
+        assert_eq!(lefts("ab\nabcd", Align::Left), [0.0, 10.0, 0.0, 10.0, 20.0, 30.0]);
+        assert_eq!(lefts("ab\nabcd", Align::Center), [10.0, 20.0, 0.0, 10.0, 20.0, 30.0]);
+        assert_eq!(lefts("ab\nabcd", Align::Right), [20.0, 30.0, 0.0, 10.0, 20.0, 30.0]);
+        // a trailing space doesnt count towards the width it lines up by
+        assert_eq!(lefts("abcd\nab ", Align::Right), [0.0, 10.0, 20.0, 30.0, 20.0, 30.0]);
     }
 }
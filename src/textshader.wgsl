@@ -1,26 +1,27 @@
-struct TextUniform {
-    size: vec2<u32>,
-    position: vec2<u32>,
-    length: u32,
-    kerning: u32,
+struct LabelData {
+    // pixels from the bottom left of the screen to the start of the baseline
+    origin: vec2<f32>,
+    visible: u32,
+    _padding: u32,
 };
 
 struct CharData {
-    atlas_offset_x: u32,
-    atlas_offset_y: u32,
-    color: vec3<f32>,
+    // x, y, width, height in atlas pixels
+    atlas_rect: vec4<u32>,
+    // x, y, width, height in pixels from the label origin
+    quad: vec4<f32>,
+    color: vec4<f32>,
+    label: u32,
+    // backgrounds fill their whole quad
+    solid: u32,
 }
 
 @group(0) @binding(0)
 var<uniform> screen_size: vec2<u32>;
 
-// visible to both vertex and fragment
-// contains metadata like
-// - character clip width x height
-// - text starting position (clip coords)
-// - array of characters
+// one per label, glyphs point at theirs
 @group(0) @binding(1)
-var<uniform> metadata: TextUniform;
+var<storage, read> labels: array<LabelData>;
 
 @group(0) @binding(2)
 var<storage, read> glyphs: array<CharData>;
@@ -32,28 +33,9 @@ var t_glyph: texture_2d<f32>;
 var s_glyph: sampler;
 
 fn pixel_to_world_coord_2d(
-    pixel: vec2<u32>,
+    pixel: vec2<f32>,
 ) -> vec2<f32> {
-    return vec2<f32>(
-        (f32(pixel[0]) / (f32(screen_size[0]) * 0.5)) - 1.0,
-        (f32(pixel[1]) / (f32(screen_size[1]) * 0.5)) - 1.0
-    );
-}
-
-fn pixel_to_world_dist_1d(
-    pixel: u32,
-) -> f32 {
-    return f32(pixel) / (f32(screen_size[0]) * 0.5);
-}
-
-fn pixel_to_world_dist_2d(
-    pixel: vec2<u32>,
-) -> vec2<f32> {
-    //return vec2<f32>(0.0, 0.0);
-    return vec2<f32>(
-        f32(pixel[0]) / (f32(screen_size[0]) * 0.5),
-        f32(pixel[1]) / (f32(screen_size[1]) * 0.5)
-    );
+    return pixel / (vec2<f32>(screen_size) * 0.5) - 1.0;
 }
 
 // ====== Vertex shader ======
@@ -63,9 +45,10 @@ struct VertexInput {
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) index: u32,
+    @location(0) @interpolate(flat) index: u32,
     @location(1) tex_coords: vec2<f32>,
-    @location(2) color: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) @interpolate(flat) solid: u32,
 };
 
 @vertex
@@ -75,25 +58,25 @@ fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
-    let world_pos = pixel_to_world_coord_2d(metadata.position);
-    // move char based on index
-    let char = model.position + vec3<f32>(f32(in_index) + (f32(in_index) * pixel_to_world_dist_1d(metadata.kerning)), 0.0, 0.0);
-    //let char = model.position + vec3<f32>(f32(in_index) + (f32(in_index) * 20.0), 0.0, 0.0);
-    //let char = model.position + vec3<f32>(f32(in_index), 0.0, 0.0);
-    // scale char to proper aspect
-    let aspect_char = char * vec3<f32>(5.0 / 12.0, 1.0, 1.0);
-    // scale char based on metadata
-    let scaled_char = aspect_char * vec3<f32>(pixel_to_world_dist_2d(metadata.size), 1.0);
-    // position char in worldspace
-    let positioned_char = scaled_char + vec3<f32>(world_pos, 0.0);
-    out.clip_position = vec4<f32>(positioned_char, 1.0);
+    let glyph = glyphs[in_index];
+    let label = labels[glyph.label];
+    // stretch the unit quad over the glyph and move it into place
+    let pixel = label.origin + glyph.quad.xy + model.position.xy * glyph.quad.zw;
+    out.clip_position = vec4<f32>(pixel_to_world_coord_2d(pixel), 0.0, 1.0);
+    // hidden labels get pushed outside the clip volume
+    if (label.visible == 0u) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
     out.index = in_index;
     // calculate texcoords here because instance_index isnt available
     // in the fragment shader because fuck you
+    let rect = glyph.atlas_rect;
+    let atlas_size = vec2<f32>(textureDimensions(t_glyph));
     out.tex_coords = vec2<f32>(
-        (model.position.x + f32(glyphs[in_index].atlas_offset_x)) / 32.0,
-        ((1.0 - model.position.y) + f32(glyphs[in_index].atlas_offset_y)) / 12.0);
-    out.color = glyphs[in_index].color;
+        f32(rect.x) + model.position.x * f32(rect.z),
+        f32(rect.y) + (1.0 - model.position.y) * f32(rect.w)) / atlas_size;
+    out.color = glyph.color;
+    out.solid = glyph.solid;
     return out;
 }
 
@@ -102,5 +85,11 @@ fn vs_main(
 fn fs_main(
     in: VertexOutput
 ) -> @location(0) vec4<f32> {
-    return (textureSample(t_glyph, s_glyph, in.tex_coords) * vec4<f32>(in.color, 1.0));
+    // the atlas is single channel coverage. sample it either way, it has
+    // to happen in uniform control flow
+    var coverage = textureSample(t_glyph, s_glyph, in.tex_coords).r;
+    if (in.solid != 0u) {
+        coverage = 1.0;
+    }
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
 }
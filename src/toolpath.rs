@@ -0,0 +1,715 @@
+use gcode::GCommand;
+
+use crate::config::Config;
+
+// a single lexed line, stripped down to the things that actually change
+// the machine state. these are cheap to build in parallel and cheap to
+// replay in order afterwards
+#[derive(Copy, Clone, Debug)]
+pub enum Op {
+    Move {
+        x: Option<f32>,
+        y: Option<f32>,
+        z: Option<f32>,
+        e: Option<f32>,
+        f: Option<f32>,
+    },
+    SetPosition {
+        x: Option<f32>,
+        y: Option<f32>,
+        z: Option<f32>,
+        e: Option<f32>,
+    },
+    Absolute,
+    Relative,
+    AbsoluteExtrusion,
+    RelativeExtrusion,
+    // from the comments slicers leave ahead of each section
+    Feature(Feature),
+    Width(f32),
+    Height(f32),
+    // the rest dont change where the nozzle goes, they end up in
+    // Toolpath::events for the planner and the reports (along with the
+    // modal ones above, for the linter)
+    //
+    // G28, which axes. none given means all of them
+    Home {
+        x: bool,
+        y: bool,
+        z: bool,
+    },
+    // M104/M109 and M140/M190, in C. the M109 and M190 ones wait for it
+    // to get there before carrying on
+    Temperature {
+        heater: Heater,
+        target: f32,
+        wait: bool,
+    },
+    // any other G or M code, so the linter can tell whats in the file
+    Code {
+        letter: char,
+        number: u32,
+    },
+    // anything else that isnt a comment, klipper macros and the like. the
+    // name is on Event::line in the source
+    Extended,
+    // T0, T1 and so on, switching extruders
+    ToolChange(usize),
+    // ;WIPE_START and ;WIPE_END, the moves in between wipe the nozzle
+    Wipe {
+        start: bool,
+    },
+    // prusa and super slicers `; printing object` and `; stop printing
+    // object`, and curas ;MESH:. the name is on Event::line in the source
+    Object {
+        start: bool,
+    },
+    //
+    // M204, mm/s^2
+    Acceleration {
+        print: Option<f32>,
+        travel: Option<f32>,
+        retract: Option<f32>,
+    },
+    // M205, mm/s. J is marlins junction deviation in mm
+    Jerk {
+        x: Option<f32>,
+        y: Option<f32>,
+        z: Option<f32>,
+        e: Option<f32>,
+        junction_deviation: Option<f32>,
+    },
+    // M203, mm/s
+    MaxFeedrate([Option<f32>; 4]),
+    // M201, mm/s^2
+    MaxAcceleration([Option<f32>; 4]),
+    // klippers SET_VELOCITY_LIMIT
+    VelocityLimit {
+        velocity: Option<f32>,
+        accel: Option<f32>,
+        accel_to_decel: Option<f32>,
+        square_corner_velocity: Option<f32>,
+    },
+    // G4, seconds
+    Dwell(f32),
+    // M73, percent done and minutes left
+    Progress {
+        percent: Option<f32>,
+        remaining: Option<f32>,
+    },
+    // what the slicer reckons the whole print takes, in seconds
+    Estimate(f32),
+}
+
+impl Op {
+    // whether this goes in Toolpath::events
+    pub fn is_event(&self) -> bool {
+        !matches!(
+            self,
+            Op::Move { .. }
+                | Op::Feature(_)
+                | Op::Width(_)
+                | Op::Height(_)
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Heater {
+    Hotend,
+    Bed,
+}
+
+// what the slicer says a move is for. prusa/super slicer and cura both
+// mark these with ;TYPE: comments, just under different names
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Feature {
+    // nothing said so far
+    None,
+    Perimeter,
+    ExternalPerimeter,
+    OverhangPerimeter,
+    ThinWall,
+    InternalInfill,
+    SolidInfill,
+    TopSolidInfill,
+    BridgeInfill,
+    InternalBridgeInfill,
+    GapFill,
+    Ironing,
+    Skirt,
+    Support,
+    SupportInterface,
+    WipeTower,
+    Custom,
+    // a name we dont know about
+    Other,
+}
+
+impl Feature {
+    pub fn parse(name: &str) -> Self {
+        match name.trim() {
+            "Perimeter" | "Internal perimeter" | "WALL-INNER" => Feature::Perimeter,
+            "External perimeter" | "WALL-OUTER" => Feature::ExternalPerimeter,
+            "Overhang perimeter" => Feature::OverhangPerimeter,
+            "Thin wall" => Feature::ThinWall,
+            "Internal infill" | "FILL" => Feature::InternalInfill,
+            "Solid infill" => Feature::SolidInfill,
+            "Top solid infill" | "SKIN" => Feature::TopSolidInfill,
+            "Bridge infill" => Feature::BridgeInfill,
+            "Internal bridge infill" => Feature::InternalBridgeInfill,
+            "Gap fill" => Feature::GapFill,
+            "Ironing" => Feature::Ironing,
+            "Skirt" | "Skirt/Brim" | "Brim" | "SKIRT" => Feature::Skirt,
+            "Support material" | "SUPPORT" => Feature::Support,
+            "Support material interface" | "SUPPORT-INTERFACE" => Feature::SupportInterface,
+            "Wipe tower" | "PRIME-TOWER" => Feature::WipeTower,
+            "Custom" => Feature::Custom,
+            _ => Feature::Other,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::None => "none",
+            Feature::Perimeter => "perimeter",
+            Feature::ExternalPerimeter => "external perimeter",
+            Feature::OverhangPerimeter => "overhang perimeter",
+            Feature::ThinWall => "thin wall",
+            Feature::InternalInfill => "internal infill",
+            Feature::SolidInfill => "solid infill",
+            Feature::TopSolidInfill => "top solid infill",
+            Feature::BridgeInfill => "bridge infill",
+            Feature::InternalBridgeInfill => "internal bridge infill",
+            Feature::GapFill => "gap fill",
+            Feature::Ironing => "ironing",
+            Feature::Skirt => "skirt",
+            Feature::Support => "support",
+            Feature::SupportInterface => "support interface",
+            Feature::WipeTower => "wipe tower",
+            Feature::Custom => "custom",
+            Feature::Other => "other",
+        }
+    }
+}
+
+// splits off the comment and anything after it
+pub fn strip_comment(line: &str) -> &str {
+    match line.find(';') {
+        Some(i) => &line[..i],
+        None => line,
+    }
+    .trim()
+}
+
+// iterates the letter/value words of a (comment stripped) line. we only
+// lean on this for the handful of modal commands the gcode crate doesnt
+// hand back as a GCommand
+pub fn words(code: &str) -> impl Iterator<Item = (char, Option<f32>)> + '_ {
+    code.split_whitespace().filter_map(|word| {
+        let letter = word.chars().next()?.to_ascii_uppercase();
+        Some((letter, word[letter.len_utf8()..].parse::<f32>().ok()))
+    })
+}
+
+// the KEY=value parameters of a klipper style extended command, like
+// SET_VELOCITY_LIMIT ACCEL=5000. keys come back upper case
+pub fn params(code: &str) -> impl Iterator<Item = (String, &str)> + '_ {
+    code.split_whitespace().skip(1).filter_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        Some((key.to_ascii_uppercase(), value))
+    })
+}
+
+// 1d 2h 3m 4s, the way prusa and super slicer write their estimates
+pub fn parse_duration(text: &str) -> Option<f32> {
+    let mut seconds = 0.0;
+    let mut any = false;
+    for part in text.split_whitespace() {
+        let unit = part.chars().last()?;
+        let value: f32 = part[..part.len() - unit.len_utf8()].parse().ok()?;
+        seconds += value * match unit {
+            'd' => 86400.0,
+            'h' => 3600.0,
+            'm' => 60.0,
+            's' => 1.0,
+            _ => return None,
+        };
+        any = true;
+    }
+    any.then_some(seconds)
+}
+
+fn axes(code: &str) -> [Option<f32>; 5] {
+    let mut axes = [None; 5];
+    for (letter, value) in words(code).skip(1) {
+        match letter {
+            'X' => axes[0] = value,
+            'Y' => axes[1] = value,
+            'Z' => axes[2] = value,
+            'E' => axes[3] = value,
+            'F' => axes[4] = value,
+            _ => {},
+        }
+    }
+    axes
+}
+
+// the ;TYPE:, ;WIDTH:, ;HEIGHT: and ;WIPE_ annotations, the rest of the
+// comments are just comments
+fn lex_annotation(comment: &str) -> Option<Op> {
+    if let Some(estimate) = comment.trim().strip_prefix("estimated printing time (normal mode) =") {
+        return parse_duration(estimate).map(Op::Estimate);
+    }
+    match comment.trim() {
+        "WIPE_START" => return Some(Op::Wipe { start: true }),
+        "WIPE_END" => return Some(Op::Wipe { start: false }),
+        comment if comment.starts_with("printing object ") => return Some(Op::Object { start: true }),
+        comment if comment.starts_with("stop printing object") => return Some(Op::Object { start: false }),
+        _ => {},
+    }
+    let (key, value) = comment.split_once(':')?;
+    match key {
+        "TYPE" => Some(Op::Feature(Feature::parse(value))),
+        "WIDTH" => value.trim().parse().ok().map(Op::Width),
+        "HEIGHT" => value.trim().parse().ok().map(Op::Height),
+        // cura, in seconds
+        "TIME" => value.trim().parse().ok().map(Op::Estimate),
+        "MESH" => Some(Op::Object { start: value.trim() != "NONMESH" }),
+        _ => None,
+    }
+}
+
+pub fn lex_line(line: &str) -> Option<Op> {
+    if let Some(comment) = line.trim_start().strip_prefix(';') {
+        return lex_annotation(comment);
+    }
+    let code = strip_comment(line);
+    if code.is_empty() {
+        return None;
+    }
+
+    // moves go through the real lexer/parser, same as they always have
+    if let Ok(Some(fields)) = gcode::lexer::lex(code) {
+        if let Ok(GCommand::G1 { x, y, z, e, f, .. }) = gcode::parser::parse(fields) {
+            return Some(Op::Move { x, y, z, e, f });
+        }
+    }
+
+    let command = code.split_whitespace().next()?;
+    if command.eq_ignore_ascii_case("SET_VELOCITY_LIMIT") {
+        let (mut velocity, mut accel, mut accel_to_decel, mut square_corner_velocity) = (None, None, None, None);
+        for (key, value) in params(code) {
+            let value = value.parse().ok();
+            match key.as_str() {
+                "VELOCITY" => velocity = value,
+                "ACCEL" => accel = value,
+                "ACCEL_TO_DECEL" => accel_to_decel = value,
+                "SQUARE_CORNER_VELOCITY" => square_corner_velocity = value,
+                _ => {},
+            }
+        }
+        return Some(Op::VelocityLimit { velocity, accel, accel_to_decel, square_corner_velocity });
+    }
+
+    let (letter, number) = words(code).next()?;
+    let Some(number) = number.filter(|_| matches!(letter, 'G' | 'M' | 'T')) else {
+        return Some(Op::Extended);
+    };
+    match (letter, Some(number as u32)) {
+        ('G', Some(0)) => {
+            let [x, y, z, e, f] = axes(code);
+            Some(Op::Move { x, y, z, e, f })
+        },
+        ('G', Some(92)) => {
+            let [x, y, z, e, _] = axes(code);
+            Some(Op::SetPosition { x, y, z, e })
+        },
+        ('G', Some(28)) => {
+            let homed = |axis| words(code).skip(1).any(|(letter, _)| letter == axis);
+            let (x, y, z) = (homed('X'), homed('Y'), homed('Z'));
+            if x || y || z {
+                Some(Op::Home { x, y, z })
+            } else {
+                Some(Op::Home { x: true, y: true, z: true })
+            }
+        },
+        ('M', Some(code_number @ (104 | 109 | 140 | 190))) => {
+            // R is marlins wait for it to cool down as well as heat up
+            let target = words(code)
+                .skip(1)
+                .find(|(letter, _)| matches!(letter, 'S' | 'R'))
+                .and_then(|(_, value)| value)?;
+            Some(Op::Temperature {
+                heater: if matches!(code_number, 104 | 109) { Heater::Hotend } else { Heater::Bed },
+                target,
+                wait: matches!(code_number, 109 | 190),
+            })
+        },
+        ('G', Some(90)) => Some(Op::Absolute),
+        ('G', Some(91)) => Some(Op::Relative),
+        ('M', Some(82)) => Some(Op::AbsoluteExtrusion),
+        ('M', Some(83)) => Some(Op::RelativeExtrusion),
+        ('T', Some(tool)) => Some(Op::ToolChange(tool as usize)),
+        ('G', Some(4)) => {
+            let mut seconds = 0.0;
+            for (letter, value) in words(code).skip(1) {
+                match (letter, value) {
+                    ('P', Some(ms)) => seconds += ms / 1000.0,
+                    ('S', Some(s)) => seconds += s,
+                    _ => {},
+                }
+            }
+            Some(Op::Dwell(seconds))
+        },
+        ('M', Some(73)) => {
+            let (mut percent, mut remaining) = (None, None);
+            for (letter, value) in words(code).skip(1) {
+                match letter {
+                    'P' => percent = value,
+                    'R' => remaining = value,
+                    _ => {},
+                }
+            }
+            Some(Op::Progress { percent, remaining })
+        },
+        ('M', Some(204)) => {
+            let (mut print, mut travel, mut retract) = (None, None, None);
+            for (letter, value) in words(code).skip(1) {
+                match letter {
+                    // S is the old way of setting both
+                    'S' => (print, travel) = (value, value),
+                    'P' => print = value,
+                    'T' => travel = value,
+                    'R' => retract = value,
+                    _ => {},
+                }
+            }
+            Some(Op::Acceleration { print, travel, retract })
+        },
+        ('M', Some(205)) => {
+            let [x, y, z, e, _] = axes(code);
+            let junction_deviation = words(code).find(|(letter, _)| *letter == 'J').and_then(|(_, value)| value);
+            Some(Op::Jerk { x, y, z, e, junction_deviation })
+        },
+        ('M', Some(203)) => {
+            let [x, y, z, e, _] = axes(code);
+            Some(Op::MaxFeedrate([x, y, z, e]))
+        },
+        ('M', Some(201)) => {
+            let [x, y, z, e, _] = axes(code);
+            Some(Op::MaxAcceleration([x, y, z, e]))
+        },
+        (letter, _) => Some(Op::Code { letter, number: number as u32 }),
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Move {
+    // 1 based line number in the source file
+    pub line: u32,
+    pub start: [f32; 3],
+    pub end: [f32; 3],
+    // extruded length for this move, negative for retractions
+    pub e: f32,
+    // mm/min
+    pub feedrate: f32,
+    pub feature: Feature,
+    // extrusion width and layer height in mm, if the slicer said
+    pub width: f32,
+    pub height: f32,
+    // which extruder, 0 for T0
+    pub tool: usize,
+}
+
+// the modal state of the printer. positions, E and feedrate carry over
+// from line to line, which is why chunks lexed in parallel still have to
+// be replayed in order
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MachineState {
+    pub position: [f32; 3],
+    pub e: f32,
+    pub feedrate: f32,
+    pub absolute: bool,
+    pub absolute_extrusion: bool,
+    pub feature: Feature,
+    pub width: f32,
+    pub height: f32,
+    pub tool: usize,
+}
+
+impl MachineState {
+    pub fn new() -> Self {
+        Self {
+            position: [0.0; 3],
+            e: 0.0,
+            feedrate: 0.0,
+            absolute: true,
+            absolute_extrusion: true,
+            feature: Feature::None,
+            width: 0.0,
+            height: 0.0,
+            tool: 0,
+        }
+    }
+
+    pub fn apply(&mut self, line: u32, op: &Op) -> Option<Move> {
+        match *op {
+            Op::Move { x, y, z, e, f } => {
+                let start = self.position;
+                for (axis, value) in [x, y, z].into_iter().enumerate() {
+                    if let Some(v) = value {
+                        self.position[axis] = if self.absolute { v } else { self.position[axis] + v };
+                    }
+                }
+                let delta_e = match e {
+                    Some(v) if self.absolute_extrusion => {
+                        let delta = v - self.e;
+                        self.e = v;
+                        delta
+                    },
+                    Some(v) => {
+                        self.e += v;
+                        v
+                    },
+                    None => 0.0,
+                };
+                if let Some(f) = f {
+                    self.feedrate = f;
+                }
+                Some(Move {
+                    line,
+                    start,
+                    end: self.position,
+                    e: delta_e,
+                    feedrate: self.feedrate,
+                    feature: self.feature,
+                    width: self.width,
+                    height: self.height,
+                    tool: self.tool,
+                })
+            },
+            Op::SetPosition { x, y, z, e } => {
+                for (axis, value) in [x, y, z].into_iter().enumerate() {
+                    if let Some(v) = value {
+                        self.position[axis] = v;
+                    }
+                }
+                if let Some(e) = e {
+                    self.e = e;
+                }
+                None
+            },
+            Op::Absolute => {
+                self.absolute = true;
+                self.absolute_extrusion = true;
+                None
+            },
+            Op::Relative => {
+                self.absolute = false;
+                self.absolute_extrusion = false;
+                None
+            },
+            Op::AbsoluteExtrusion => {
+                self.absolute_extrusion = true;
+                None
+            },
+            Op::RelativeExtrusion => {
+                self.absolute_extrusion = false;
+                None
+            },
+            Op::Feature(feature) => {
+                self.feature = feature;
+                None
+            },
+            Op::Width(width) => {
+                self.width = width;
+                None
+            },
+            Op::Height(height) => {
+                self.height = height;
+                None
+            },
+            Op::ToolChange(tool) => {
+                self.tool = tool;
+                None
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Default for MachineState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Bounds {
+    pub fn at(point: [f32; 3]) -> Self {
+        Self { min: point, max: point }
+    }
+
+    pub fn include(&mut self, point: [f32; 3]) {
+        for (axis, value) in point.into_iter().enumerate() {
+            self.min[axis] = self.min[axis].min(value);
+            self.max[axis] = self.max[axis].max(value);
+        }
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        [
+            (self.min[0] + self.max[0]) * 0.5,
+            (self.min[1] + self.max[1]) * 0.5,
+            (self.min[2] + self.max[2]) * 0.5,
+        ]
+    }
+
+    pub fn size(&self) -> [f32; 3] {
+        [
+            self.max[0] - self.min[0],
+            self.max[1] - self.min[1],
+            self.max[2] - self.min[2],
+        ]
+    }
+}
+
+impl Move {
+    pub fn is_extrusion(&self) -> bool {
+        self.e > 0.0 && (self.start[0] != self.end[0] || self.start[1] != self.end[1])
+    }
+
+    pub fn length(&self) -> f32 {
+        let [dx, dy, dz] = [
+            self.end[0] - self.start[0],
+            self.end[1] - self.start[1],
+            self.end[2] - self.start[2],
+        ];
+        (dx * dx + dy * dy + dz * dz).sqrt()
+    }
+}
+
+// an op that doesnt move anything, with where it sat in the file
+#[derive(Copy, Clone, Debug)]
+pub struct Event {
+    pub line: u32,
+    // the index of the move that comes after it
+    pub before: usize,
+    pub op: Op,
+    // came out of a macro the line calls rather than from the line itself,
+    // so the text at line isnt this op
+    pub expanded: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub z: f32,
+    // indices into Toolpath::moves
+    pub moves: std::ops::Range<usize>,
+}
+
+pub struct Toolpath {
+    pub moves: Vec<Move>,
+    pub events: Vec<Event>,
+    pub config: Config,
+}
+
+impl Toolpath {
+    pub fn new() -> Self {
+        Self {
+            moves: Vec::new(),
+            events: Vec::new(),
+            config: Config::new(),
+        }
+    }
+
+    // adds whatever the op turned into
+    pub fn push(&mut self, line: u32, op: Op, state: &mut MachineState) {
+        self.push_op(line, op, state, false);
+    }
+
+    // the same for an op a macro called on that line would run
+    pub fn push_expanded(&mut self, line: u32, op: Op, state: &mut MachineState) {
+        self.push_op(line, op, state, true);
+    }
+
+    fn push_op(&mut self, line: u32, op: Op, state: &mut MachineState, expanded: bool) {
+        if let Some(m) = state.apply(line, &op) {
+            self.moves.push(m);
+        } else if op.is_event() {
+            self.events.push(Event { line, before: self.moves.len(), op, expanded });
+        }
+    }
+
+    // the path as a connected strip: where the first move started, then
+    // wherever every move ended up
+    pub fn points(&self) -> impl Iterator<Item = [f32; 3]> + '_ {
+        self.moves.first()
+            .map(|m| m.start)
+            .into_iter()
+            .chain(self.moves.iter().map(|m| m.end))
+    }
+
+    pub fn bounds(&self) -> Option<Bounds> {
+        let mut points = self.points();
+        let mut bounds = Bounds::at(points.next()?);
+        for point in points {
+            bounds.include(point);
+        }
+        Some(bounds)
+    }
+
+    // splits the moves wherever extrusion starts happening at a new height.
+    // the z move (and any travel after it) belongs to the layer it lifted
+    // to, so z hops dont start a layer of their own
+    pub fn layers(&self) -> Vec<Layer> {
+        let mut layers = Vec::<Layer>::new();
+        let mut z_change = 0;
+        for (i, m) in self.moves.iter().enumerate() {
+            if m.start[2] != m.end[2] {
+                z_change = i;
+            }
+            if !m.is_extrusion() {
+                continue;
+            }
+            match layers.last_mut() {
+                Some(layer) if layer.z == m.end[2] => {},
+                Some(layer) => {
+                    layer.moves.end = z_change;
+                    layers.push(Layer { z: m.end[2], moves: z_change..z_change });
+                },
+                None => layers.push(Layer { z: m.end[2], moves: 0..0 }),
+            }
+        }
+        if let Some(layer) = layers.last_mut() {
+            layer.moves.end = self.moves.len();
+        }
+        layers
+    }
+
+    // the move a source line belongs to, which is the last one at or
+    // before it so comments and the like point at the move they follow
+    pub fn move_at_line(&self, line: u32) -> Option<usize> {
+        self.moves.partition_point(|m| m.line <= line).checked_sub(1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+}
+
+impl Default for Toolpath {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,119 @@
+use std::ops::Range;
+
+use crate::config::Config;
+use crate::toolpath::{Op, Toolpath};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ToolChange {
+    pub from: usize,
+    pub to: usize,
+    // where the nozzle was when it switched, None if nothing had moved yet
+    pub position: Option<[f32; 3]>,
+    // index into Toolpath::moves of the first move with the new tool
+    pub index: usize,
+    pub line: u32,
+}
+
+// every T code that actually switches to a different extruder. the first
+// one just picks which extruder to start with, whichever it is, and
+// slicers like to select the same one again, which doesnt count either
+pub fn changes(toolpath: &Toolpath) -> Vec<ToolChange> {
+    let mut changes = Vec::new();
+    let mut tool = None;
+    for event in toolpath.events.iter() {
+        let Op::ToolChange(to) = event.op else { continue };
+        let Some(from) = tool.replace(to) else { continue };
+        if to == from {
+            continue;
+        }
+        let position = event.before.checked_sub(1).and_then(|i| toolpath.moves.get(i)).map(|m| m.end);
+        changes.push(ToolChange { from, to, position, index: event.before, line: event.line });
+    }
+    changes
+}
+
+// the moves split up by extruder, so each run can be drawn in one go
+pub fn runs(toolpath: &Toolpath) -> Vec<(Range<usize>, usize)> {
+    let mut runs: Vec<(Range<usize>, usize)> = Vec::new();
+    for (i, m) in toolpath.moves.iter().enumerate() {
+        match runs.last_mut() {
+            Some((run, tool)) if *tool == m.tool => run.end = i + 1,
+            _ => runs.push((i..i + 1, m.tool)),
+        }
+    }
+    runs
+}
+
+// prusa/super slicers `extruder_offset`, `0x0,20x0` with one xy per
+// extruder
+pub fn offsets_from_config(config: &Config) -> Vec<[f32; 2]> {
+    let Some(offsets) = config.get("extruder_offset") else {
+        return Vec::new();
+    };
+    offsets
+        .split(',')
+        .map(|offset| {
+            let (x, y) = offset.trim().split_once('x')?;
+            Some([x.trim().parse().ok()?, y.trim().parse().ok()?])
+        })
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default()
+}
+
+// moves everything printed by the other extruders to where their nozzles
+// actually are. the slicer takes the offset off the coordinates it writes
+// so the printer puts the plastic in the right place, this puts it back.
+// each move starts where the last one ended, so the jump to the new
+// nozzle happens on the first move after a tool change (normally the
+// travel to wherever it prints next) rather than in between two moves
+pub fn apply_offsets(toolpath: &mut Toolpath, offsets: &[[f32; 2]]) {
+    let offset = |tool: usize| offsets.get(tool).copied().unwrap_or([0.0; 2]);
+    let mut previous = toolpath.moves.first().map_or(0, |m| m.tool);
+    for m in toolpath.moves.iter_mut() {
+        for (point, [x, y]) in [(&mut m.start, offset(previous)), (&mut m.end, offset(m.tool))] {
+            point[0] += x;
+            point[1] += y;
+        }
+        previous = m.tool;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+
+    #[test]
+    fn changes_start_from_the_first_tool_selected() {
+        let toolpath = loader::load_bytes(b"T1\nG1 X10 Y10 F3000\nT1\nG1 X20 Y10 E1\nT0\nG1 X30 Y10 E1\n");
+        let changes = changes(&toolpath);
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].from, changes[0].to, changes[0].index), (1, 0, 2));
+        assert_eq!(changes[0].position, Some([20.0, 10.0, 0.0]));
+    }
+
+    #[test]
+    fn offsets_move_the_other_tools_moves_without_a_gap() {
+        let mut toolpath = loader::load_bytes(b"T0\nG1 X10 Y10 F3000\nG1 X20 Y10 E1\nT1\nG1 X30 Y10\nG1 X40 Y10 E1\n");
+        apply_offsets(&mut toolpath, &[[0.0, 0.0], [20.0, 5.0]]);
+        let moves: Vec<_> = toolpath.moves.iter().map(|m| (m.start, m.end)).collect();
+        assert_eq!(moves[1], ([10.0, 10.0, 0.0], [20.0, 10.0, 0.0]));
+        // the travel after T1 goes from the first nozzle to the second
+        assert_eq!(moves[2], ([20.0, 10.0, 0.0], [50.0, 15.0, 0.0]));
+        assert_eq!(moves[3], ([50.0, 15.0, 0.0], [60.0, 15.0, 0.0]));
+        // so the path drawn as a strip goes through every move
+        let points: Vec<_> = toolpath.points().collect();
+        assert_eq!(points.len(), moves.len() + 1);
+        for (i, (start, end)) in moves.iter().enumerate() {
+            assert_eq!((points[i], points[i + 1]), (*start, *end));
+        }
+    }
+
+    #[test]
+    fn change_before_any_move_has_no_position() {
+        let toolpath = loader::load_bytes(b"T0\nT1\nG1 X10 Y10 F3000\n");
+        let changes = changes(&toolpath);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].position, None);
+    }
+}
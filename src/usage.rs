@@ -0,0 +1,53 @@
+use fig::stats::{Stats, Usage};
+
+use crate::hud;
+use crate::text::{self, Anchor};
+
+// filament used by the whole print, the layer being looked at, each
+// feature and each extruder, in the bottom right corner
+pub struct UsagePanel {
+    pub panel: hud::Panel,
+}
+
+impl UsagePanel {
+    pub fn new(text: &mut text::Layer) -> Self {
+        Self { panel: hud::Panel::new(text, Anchor::BottomRight) }
+    }
+
+    // call once per frame
+    pub fn update(&mut self, text: &mut text::Layer, stats: &Stats, layer: Option<usize>) {
+        if !self.panel.refresh(layer) {
+            return;
+        }
+
+        let mut rows = vec![("total", format_usage(&stats.total))];
+        if let Some((l, usage)) = layer.and_then(|l| stats.layers.get(l).map(|usage| (l, usage))) {
+            rows.push(("layer", format!("{}  {}", l + 1, format_usage(usage))));
+        }
+        for (feature, usage) in stats.features.iter() {
+            rows.push((feature.name(), format_usage(usage)));
+        }
+        // one extruder is already the total
+        let extruders: Vec<String> = (0..stats.extruders.len()).map(|i| format!("T{}", i)).collect();
+        if stats.extruders.len() > 1 {
+            for (name, usage) in extruders.iter().zip(stats.extruders.iter()) {
+                rows.push((name, format_usage(usage)));
+            }
+        }
+        self.panel.set_rows(text, &rows);
+    }
+}
+
+// 2.84m  6.84cm3  7.05g  0.15
+fn format_usage(usage: &Usage) -> String {
+    let length = if usage.length >= 1000.0 {
+        format!("{:.2}m", usage.length / 1000.0)
+    } else {
+        format!("{:.1}mm", usage.length)
+    };
+    let mut text = format!("{}  {:.2}cm3  {:.2}g", length, usage.volume / 1000.0, usage.weight);
+    if usage.cost > 0.0 {
+        text += &format!("  {:.2}", usage.cost);
+    }
+    text
+}
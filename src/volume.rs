@@ -0,0 +1,106 @@
+use crate::config::Config;
+use crate::toolpath::{Bounds, Toolpath};
+
+// how far past the edge still counts as inside, for rounding in the gcode
+const TOLERANCE: f32 = 0.01;
+
+// the build volume from prusa/super slicers `bed_shape` (the corners of
+// the bed, `0x0,300x0,300x300,0x300`) and `max_print_height`
+pub fn from_config(config: &Config) -> Option<Bounds> {
+    let mut corners = config.get("bed_shape")?.split(',').map(|corner| {
+        let (x, y) = corner.trim().split_once('x')?;
+        Some([x.trim().parse().ok()?, y.trim().parse().ok()?, 0.0])
+    });
+    let mut volume = Bounds::at(corners.next()??);
+    for corner in corners {
+        volume.include(corner?);
+    }
+    volume.max[2] = config.get_f32("max_print_height")?;
+    Some(volume)
+}
+
+// a volume from the command line, `300x300x250` from the origin or
+// `-150,-150,0:150,150,300` for beds that dont start there
+pub fn parse(text: &str) -> Option<Bounds> {
+    let point = |text: &str, separator| -> Option<[f32; 3]> {
+        let mut values = text.split(separator).map(|v| v.trim().parse().ok());
+        let point = [values.next()??, values.next()??, values.next()??];
+        values.next().is_none().then_some(point)
+    };
+    match text.split_once(':') {
+        Some((min, max)) => Some(Bounds { min: point(min, ',')?, max: point(max, ',')? }),
+        None => Some(Bounds { min: [0.0; 3], max: point(text, 'x')? }),
+    }
+}
+
+pub fn contains(volume: &Bounds, point: [f32; 3]) -> bool {
+    (0..3).all(|axis| {
+        point[axis] >= volume.min[axis] - TOLERANCE && point[axis] <= volume.max[axis] + TOLERANCE
+    })
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Check {
+    // what gets printed and where the nozzle goes in between
+    pub extrusion: Option<Bounds>,
+    pub travel: Option<Bounds>,
+    // indices into Toolpath::moves that leave the volume
+    pub outside: Vec<usize>,
+}
+
+// every move with an end outside the volume. the volume is a box so if
+// both ends are inside the whole move is
+pub fn check(toolpath: &Toolpath, volume: &Bounds) -> Check {
+    let mut check = Check::default();
+    for (i, m) in toolpath.moves.iter().enumerate() {
+        let bounds = if m.is_extrusion() { &mut check.extrusion } else { &mut check.travel };
+        let bounds = bounds.get_or_insert(Bounds::at(m.start));
+        bounds.include(m.start);
+        bounds.include(m.end);
+        if !contains(volume, m.start) || !contains(volume, m.end) {
+            check.outside.push(i);
+        }
+    }
+    check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+
+    #[test]
+    fn parse_from_the_origin_or_between_two_corners() {
+        assert_eq!(parse("300x300x250"), Some(Bounds { min: [0.0; 3], max: [300.0, 300.0, 250.0] }));
+        assert_eq!(
+            parse("-150,-150,0:150,150,300"),
+            Some(Bounds { min: [-150.0, -150.0, 0.0], max: [150.0, 150.0, 300.0] }),
+        );
+        assert_eq!(parse("300x300"), None);
+        assert_eq!(parse("300x300x250x10"), None);
+        assert_eq!(parse("0,0,0:300x300x250"), None);
+        assert_eq!(parse("big"), None);
+    }
+
+    #[test]
+    fn from_config_takes_the_bed_corners_and_print_height() {
+        let mut config = Config::new();
+        config.insert("bed_shape", "-5x-5, 245x-5,245x215,-5x215");
+        assert_eq!(from_config(&config), None);
+
+        config.insert("max_print_height", "220");
+        assert_eq!(from_config(&config), Some(Bounds { min: [-5.0, -5.0, 0.0], max: [245.0, 215.0, 220.0] }));
+
+        config.insert("bed_shape", "0x0,250xdeep");
+        assert_eq!(from_config(&config), None);
+    }
+
+    #[test]
+    fn check_finds_moves_leaving_the_volume() {
+        let toolpath = loader::load_bytes(b"G1 X10 Y10 Z0.2\nG1 X100 Y10 E5\nG1 X300.005 Y10\nG1 X301 Y10\nG1 X10 Y10\n");
+        let volume = Bounds { min: [0.0; 3], max: [300.0, 300.0, 250.0] };
+        // the one reaching past the edge and the one coming back from it,
+        // but not the one within rounding of the edge
+        assert_eq!(check(&toolpath, &volume).outside, vec![3, 4]);
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+// no point hitting the filesystem every frame
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+// slicers write big files in pieces, so wait for it to sit still for a bit
+// before we decide its done
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Stamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+// polls a file for changes. there are crates that do this with inotify and
+// friends but the debouncing ends up being the same amount of code anyway
+pub struct Watcher {
+    path: PathBuf,
+    loaded: Option<Stamp>,
+    // a stamp that differs from the loaded one, and when we first saw it
+    changed: Option<(Stamp, Instant)>,
+    // the stamp poll last asked for a reload of, until the reload works
+    settled: Option<Stamp>,
+    last_poll: Instant,
+}
+
+impl Watcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            loaded: Stamp::of(&path),
+            path,
+            changed: None,
+            settled: None,
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // returns true once per rewrite, after the file has stopped changing.
+    // until reloaded is called it keeps coming back true every time the
+    // file settles again, so a reload that fails gets tried again
+    pub fn poll(&mut self) -> bool {
+        let now = Instant::now();
+        if now.duration_since(self.last_poll) < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = now;
+
+        // a missing file is most likely halfway through being replaced
+        let Some(stamp) = Stamp::of(&self.path) else {
+            self.changed = None;
+            return false;
+        };
+
+        if self.loaded == Some(stamp) {
+            self.changed = None;
+            return false;
+        }
+
+        match self.changed {
+            Some((changed, since)) if changed == stamp => {
+                if now.duration_since(since) < SETTLE_TIME {
+                    return false;
+                }
+                self.settled = Some(stamp);
+                self.changed = None;
+                true
+            },
+            _ => {
+                self.changed = Some((stamp, now));
+                false
+            },
+        }
+    }
+
+    // the file poll asked for a reload of has been loaded
+    pub fn reloaded(&mut self) {
+        if let Some(stamp) = self.settled.take() {
+            self.loaded = Some(stamp);
+        }
+    }
+}